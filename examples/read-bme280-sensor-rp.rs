@@ -3,9 +3,8 @@
 
 use defmt::{error, info};
 use defmt_rtt as _;
-use embassy_bme280_sensor::bme280_rp::BME280Sensor;
+use embassy_bme280_sensor::bme280_rp::BME280Device;
 use embassy_bme280_sensor::configuration::{Filter, Oversampling, SamplingConfiguration, SensorMode, StandbyDuration};
-use embassy_bme280_sensor::BME280Error;
 use embassy_executor::Spawner;
 use embassy_rp::peripherals::I2C0;
 use embassy_rp::{bind_interrupts, i2c};
@@ -27,11 +26,11 @@ async fn main(_spawner: Spawner) -> ! {
     let mut i2c = i2c::I2c::new_async(p.I2C0, scl, sda, Irqs, Default::default());
 
     // Create sensor instance
-    let mut sensor = BME280Sensor::new(0x76);
+    let mut sensor = BME280Device::new(&mut i2c, 0x76);
 
     // Configure and initialize sensor
     match sensor
-        .setup(&mut i2c,
+        .setup(
             SamplingConfiguration::default()
                 .with_temperature_oversampling(Oversampling::X4)
                 .with_pressure_oversampling(Oversampling::X4)
@@ -44,26 +43,22 @@ async fn main(_spawner: Spawner) -> ! {
         .await {
         Ok(_) => info!("BME280 sensor initialized successfully"),
         Err(e) => {
-            error!("Failed to initialize BME280 sensor: ");
+            error!("Failed to initialize BME280 sensor: {}", e);
         }
     }
 
     // Read sensor data
     loop {
-        match sensor.read(&mut i2c).await {
+        match sensor.read().await {
             Ok(data) => {
                 info!(
                     "Temperature: {}°C, Humidity: {}%, Pressure: {} Pa",
-                    data.temperature, data.humidity, data.pressure
+                    data.temperature.map(|t| t.0),
+                    data.humidity.map(|h| h.0),
+                    data.pressure.map(|p| p.0)
                 );
             }
-            Err(e) => match e {
-                BME280Error::NoData => error!("No data"),
-                BME280Error::I2CError => error!("I2C communication error"),
-                BME280Error::InvalidChipId(id) => error!("Invalid chip ID: {}", id),
-                BME280Error::Timeout => error!("Operation timed out"),
-                _ => error!("Other error"),
-            },
+            Err(e) => error!("Failed to read BME280 sensor: {}", e),
         }
 
         Timer::after(Duration::from_secs(1)).await;
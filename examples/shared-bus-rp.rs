@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+use defmt::{error, info};
+use defmt_rtt as _;
+use embassy_bme280_sensor::bme280_rp::{BME280Sensor, RpI2c, SharedRpI2c};
+use embassy_bme280_sensor::configuration::{Oversampling, SamplingConfiguration, SensorMode};
+use embassy_bme280_sensor::shared_bus::{I2cDevice, SharedI2cBus};
+use embassy_executor::Spawner;
+use embassy_rp::peripherals::I2C0;
+use embassy_rp::{bind_interrupts, i2c};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::once_lock::OnceLock;
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+use panic_probe as _;
+
+bind_interrupts!(struct Irqs {
+    I2C0_IRQ => i2c::InterruptHandler<I2C0>;
+});
+
+type SharedI2c = SharedRpI2c<'static, CriticalSectionRawMutex, I2C0>;
+
+static I2C_BUS: OnceLock<SharedI2cBus<CriticalSectionRawMutex, RpI2c<'static, I2C0>>> =
+    OnceLock::new();
+
+/// DS3231 real time clock sharing the bus with both sensors.
+const RTC_ADDRESS: u8 = 0x68;
+
+#[embassy_executor::task(pool_size = 2)]
+async fn sensor_task(name: &'static str, address: u8, mut i2c: SharedI2c) -> ! {
+    let mut sensor = BME280Sensor::new(address);
+    let configuration = SamplingConfiguration::default()
+        .with_temperature_oversampling(Oversampling::X1)
+        .with_pressure_oversampling(Oversampling::X1)
+        .with_humidity_oversampling(Oversampling::X1)
+        .with_sensor_mode(SensorMode::Forced);
+    while let Err(e) = sensor.setup(&mut i2c, configuration.clone()).await {
+        error!("{}: setup failed: {}", name, e);
+        Timer::after(Duration::from_secs(5)).await;
+    }
+
+    loop {
+        match sensor.measure_once(&mut i2c).await {
+            Ok(data) => info!(
+                "{}: {}°C, {}%",
+                name,
+                data.temperature.map(|t| t.0),
+                data.humidity.map(|h| h.0)
+            ),
+            Err(e) => error!("{}: read failed: {}", name, e),
+        }
+        Timer::after(Duration::from_secs(2)).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn rtc_task(mut i2c: SharedI2c) -> ! {
+    loop {
+        let mut seconds = [0u8];
+        match i2c.write_read(RTC_ADDRESS, &[0x00], &mut seconds).await {
+            Ok(_) => info!("RTC seconds: {:x}", seconds[0]),
+            Err(_) => error!("RTC read failed"),
+        }
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let i2c = i2c::I2c::new_async(p.I2C0, p.PIN_1, p.PIN_0, Irqs, Default::default());
+    let bus = I2C_BUS.get_or_init(|| SharedI2cBus::new(i2c));
+
+    spawner
+        .spawn(sensor_task("indoor", 0x76, I2cDevice::new(bus)))
+        .unwrap();
+    spawner
+        .spawn(sensor_task("outdoor", 0x77, I2cDevice::new(bus)))
+        .unwrap();
+    spawner.spawn(rtc_task(I2cDevice::new(bus))).unwrap();
+}
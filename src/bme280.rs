@@ -0,0 +1,1472 @@
+use crate::calibration::{CalibrationRegisters, Compensation};
+use crate::configuration::{
+    Config, ControlHumidity, ControlMeasurement, Filter, Oversampling, SamplingConfiguration,
+    SensorMode, StandbyDuration,
+};
+use crate::derived::altitude;
+use crate::interface::{Address, I2cInterface, Interface, SpiInterface};
+use crate::units::Pascal;
+use crate::BME280Error::NotCalibrated;
+use crate::{
+    BME280Error, BME280IntegerResponse, BME280RawResponse, BME280Response, ChipModel,
+    BME280_ADC_H_SKIPPED, BME280_REGISTER_CHIPID, BME280_REGISTER_CONFIG, BME280_REGISTER_CONTROL,
+    BME280_REGISTER_CONTROLHUMID, BME280_REGISTER_DATA_LENGTH, BME280_REGISTER_DATA_START,
+    BME280_REGISTER_DIG_FIRST_LENGTH, BME280_REGISTER_DIG_SECOND_LENGTH, BME280_REGISTER_SOFTRESET,
+    BME280_REGISTER_STATUS, BME280_SOFTRESET_COMMAND, BME280_STATUS_IM_UPDATE,
+    BME280_STATUS_MEASURING, BMP280_REGISTER_DATA_LENGTH,
+};
+use embassy_time::{with_timeout, Duration, Timer};
+
+const BME280_CONFIG_SPI3W_EN: u8 = 0b1;
+const BME280_CALIBRATION_READ_ATTEMPTS: usize = 3;
+const BME280_CONFIGURATION_WRITE_ATTEMPTS: usize = 3;
+
+/// Bits of each configuration register that read back as written.
+const BME280_CONTROLHUMID_MASK: u8 = 0b0000_0111;
+const BME280_CONFIG_MASK: u8 = 0b1111_1101;
+const BME280_CONTROL_OVERSAMPLING_MASK: u8 = 0b1111_1100;
+
+pub struct BME280Sensor<IF = I2cInterface> {
+    interface: IF,
+    calibration_registers: Option<CalibrationRegisters>,
+    /// Set by `with_calibration_registers`, cleared by the next `setup`.
+    saved_calibration: bool,
+    sampling_configuration: SamplingConfiguration,
+    chip_model: Option<ChipModel>,
+    compensation: Compensation,
+    sea_level_pressure: Option<Pascal>,
+    verify_writes: bool,
+}
+
+impl BME280Sensor<I2cInterface> {
+    /// Accepts an [`Address`](crate::interface::Address) or a raw 7-bit
+    /// address.
+    pub fn new(address: impl Into<Address>) -> Self {
+        Self::with_interface(I2cInterface::new(address))
+    }
+}
+
+impl BME280Sensor<SpiInterface> {
+    pub fn new_spi() -> Self {
+        Self::with_interface(SpiInterface)
+    }
+}
+
+impl<IF> BME280Sensor<IF> {
+    pub fn with_interface(interface: IF) -> Self {
+        Self {
+            interface,
+            calibration_registers: None,
+            saved_calibration: false,
+            sampling_configuration: SamplingConfiguration::default(),
+            chip_model: None,
+            compensation: Compensation::default(),
+            sea_level_pressure: None,
+            verify_writes: false,
+        }
+    }
+
+    /// Reads back every configuration register write and repeats it when
+    /// the chip did not take it, up to three times before failing with
+    /// [`BME280Error::ConfigurationNotAccepted`]. A forced mode write is
+    /// only taken when the read back shows that a conversion started.
+    pub fn with_write_verification(mut self, verify_writes: bool) -> Self {
+        self.verify_writes = verify_writes;
+        self
+    }
+
+    /// Selects the compensation formulas used by `read` and `measure_once`.
+    pub fn with_compensation(mut self, compensation: Compensation) -> Self {
+        self.compensation = compensation;
+        self
+    }
+
+    /// Makes `read` and `measure_once` report the barometric altitude
+    /// relative to `sea_level_pressure`.
+    pub fn with_sea_level_pressure(mut self, sea_level_pressure: Pascal) -> Self {
+        self.set_sea_level_pressure(Some(sea_level_pressure));
+        self
+    }
+
+    /// Updates the altitude reference, e.g. with a new QNH, or disables the
+    /// altitude output with `None`.
+    pub fn set_sea_level_pressure(&mut self, sea_level_pressure: Option<Pascal>) {
+        self.sea_level_pressure = sea_level_pressure;
+    }
+
+    pub fn sea_level_pressure(&self) -> Option<Pascal> {
+        self.sea_level_pressure
+    }
+
+    /// Uses previously saved calibration coefficients.
+    ///
+    /// The next `setup` then skips the soft reset and the NVM read, which is
+    /// what a device waking from deep sleep with the sensor still powered
+    /// wants. Later `setup` calls reset the chip again.
+    pub fn with_calibration_registers(
+        mut self,
+        calibration_registers: CalibrationRegisters,
+    ) -> Self {
+        self.calibration_registers = Some(calibration_registers);
+        self.saved_calibration = true;
+        self
+    }
+
+    /// Sensor variant detected by `setup`.
+    pub fn chip_model(&self) -> Option<ChipModel> {
+        self.chip_model
+    }
+
+    fn has_humidity(&self) -> bool {
+        self.chip_model
+            .is_some_and(|chip_model| chip_model.has_humidity())
+    }
+
+    /// Checks the chip id, soft resets the chip, reads the calibration
+    /// coefficients and applies `sampling_configuration`.
+    ///
+    /// Reset and coefficient read are skipped by the first `setup` after
+    /// [`with_calibration_registers`](Self::with_calibration_registers).
+    pub async fn setup<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        sampling_configuration: SamplingConfiguration,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        // In 3-wire SPI the chip answers on SDI only once spi3w_en is set.
+        let spi3w = sampling_configuration.spi3w();
+        if spi3w {
+            self.write_register_8u(bus, BME280_REGISTER_CONFIG, BME280_CONFIG_SPI3W_EN)
+                .await?;
+        }
+        let chip_id = self.read_register_u8(bus, BME280_REGISTER_CHIPID).await?;
+        let Some(chip_model) = ChipModel::from_chip_id(chip_id) else {
+            return Err(BME280Error::InvalidChipId(chip_id));
+        };
+        self.chip_model = Some(chip_model);
+        // Saved coefficients that do not pass the check are read again.
+        let saved_calibration = core::mem::take(&mut self.saved_calibration)
+            && self
+                .calibration_registers
+                .as_ref()
+                .is_some_and(|calibration_registers| {
+                    calibration_registers.is_plausible(chip_model)
+                });
+        if !saved_calibration {
+            self.calibration_registers = None;
+            self.reset(bus, spi3w).await?;
+            self.read_coefficients(bus).await?;
+        }
+        self.set_sampling_configuration(bus, sampling_configuration)
+            .await?;
+        Timer::after(self.sampling_configuration.max_measurement_time()).await;
+        Ok(())
+    }
+
+    async fn reset<BUS>(&mut self, bus: &mut BUS, spi3w: bool) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        self.write_register_8u(bus, BME280_REGISTER_SOFTRESET, BME280_SOFTRESET_COMMAND)
+            .await?;
+        Timer::after(Duration::from_millis(10)).await;
+        if spi3w {
+            self.write_register_8u(bus, BME280_REGISTER_CONFIG, BME280_CONFIG_SPI3W_EN)
+                .await?;
+        }
+        let timeout = with_timeout(Duration::from_secs(1), async {
+            while let Ok(true) = self.is_reading_calibration(bus).await {
+                Timer::after(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        if timeout.is_err() {
+            return Err(BME280Error::Timeout);
+        }
+        Ok(())
+    }
+
+    async fn is_reading_calibration<BUS>(&mut self, bus: &mut BUS) -> Result<bool, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let status = self.read_register_u8(bus, BME280_REGISTER_STATUS).await?;
+        Ok((status & BME280_STATUS_IM_UPDATE) != 0)
+    }
+
+    async fn is_measuring<BUS>(&mut self, bus: &mut BUS) -> Result<bool, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let status = self.read_register_u8(bus, BME280_REGISTER_STATUS).await?;
+        Ok((status & BME280_STATUS_MEASURING) != 0)
+    }
+
+    /// Reads the coefficient block twice and accepts it only when both
+    /// copies match and pass [`CalibrationRegisters::is_plausible`].
+    async fn read_coefficients<BUS>(&mut self, bus: &mut BUS) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        for _ in 0..BME280_CALIBRATION_READ_ATTEMPTS {
+            let first = self.read_coefficient_block(bus).await?;
+            let second = self.read_coefficient_block(bus).await?;
+            if first != second {
+                continue;
+            }
+            let calibration_registers = CalibrationRegisters::from(first);
+            if self
+                .chip_model
+                .is_some_and(|chip_model| calibration_registers.is_plausible(chip_model))
+            {
+                self.calibration_registers = Some(calibration_registers);
+                return Ok(());
+            }
+        }
+        Err(BME280Error::InvalidCalibration)
+    }
+
+    async fn read_coefficient_block<BUS>(
+        &mut self,
+        bus: &mut BUS,
+    ) -> Result<[u8; CalibrationRegisters::LENGTH], BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let mut data = [0u8; CalibrationRegisters::LENGTH];
+        self.read_registers_bulk(bus, 0x88, &mut data[0..BME280_REGISTER_DIG_FIRST_LENGTH])
+            .await?;
+        // The humidity block is absent on BMP280, its coefficients stay zero.
+        if self.has_humidity() {
+            self.read_registers_bulk(
+                bus,
+                0xE1,
+                &mut data[BME280_REGISTER_DIG_FIRST_LENGTH
+                    ..BME280_REGISTER_DIG_FIRST_LENGTH + BME280_REGISTER_DIG_SECOND_LENGTH],
+            )
+            .await?;
+        }
+        Ok(data)
+    }
+
+    async fn set_sampling_configuration<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        sampling_configuration: SamplingConfiguration,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let sampling_configuration = if self.has_humidity() {
+            sampling_configuration
+        } else {
+            sampling_configuration.with_humidity_oversampling(Oversampling::Skip)
+        };
+        let (config, ctrl_meas, ctrl_hum) = sampling_configuration.to_low_level_configuration();
+
+        self.write_configuration_register(bus, BME280_REGISTER_CONTROL, SensorMode::Sleep as u8)
+            .await?;
+        if self.has_humidity() {
+            self.write_configuration_register(bus, BME280_REGISTER_CONTROLHUMID, ctrl_hum.into())
+                .await?;
+        }
+        self.write_configuration_register(bus, BME280_REGISTER_CONFIG, config.into())
+            .await?;
+        self.write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+            .await?;
+        self.sampling_configuration = sampling_configuration;
+        Ok(())
+    }
+
+    /// Applies a new configuration without the soft reset and calibration
+    /// read of `setup`.
+    ///
+    /// The chip is put to sleep first, since config writes may be ignored
+    /// in normal mode, and ctrl_meas is written last so a ctrl_hum change
+    /// takes effect. The 3-wire SPI setting of `setup` is kept.
+    pub async fn reconfigure<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        sampling_configuration: SamplingConfiguration,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        if self.calibration_registers.is_none() {
+            return Err(NotCalibrated);
+        }
+        let spi3w = self.sampling_configuration.spi3w();
+        self.set_sampling_configuration(bus, sampling_configuration.with_spi3w(spi3w))
+            .await
+    }
+
+    /// Changes the sensor mode only, with a single ctrl_meas write.
+    ///
+    /// The chip has no transition from normal to forced mode, so that change
+    /// writes sleep mode first.
+    pub async fn set_sensor_mode<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        sensor_mode: SensorMode,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        if self.calibration_registers.is_none() {
+            return Err(NotCalibrated);
+        }
+        let sampling_configuration = self
+            .sampling_configuration
+            .clone()
+            .with_sensor_mode(sensor_mode);
+        if self.sampling_configuration.sensor_mode() == SensorMode::Normal
+            && sensor_mode == SensorMode::Forced
+        {
+            let (_, ctrl_meas, _) = sampling_configuration
+                .clone()
+                .with_sensor_mode(SensorMode::Sleep)
+                .to_low_level_configuration();
+            self.write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+                .await?;
+        }
+        let (_, ctrl_meas, _) = sampling_configuration.to_low_level_configuration();
+        self.write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+            .await?;
+        self.sampling_configuration = sampling_configuration;
+        Ok(())
+    }
+
+    /// Changes the IIR filter only.
+    pub async fn set_filter<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        filter: Filter,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let sampling_configuration = self.sampling_configuration.clone().with_filter(filter);
+        self.set_config(bus, sampling_configuration).await
+    }
+
+    /// Changes the normal mode standby time only.
+    pub async fn set_standby_duration<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        standby_duration: StandbyDuration,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let sampling_configuration = self
+            .sampling_configuration
+            .clone()
+            .with_standby_duration(standby_duration);
+        self.set_config(bus, sampling_configuration).await
+    }
+
+    /// Writes the config register in sleep mode and restores ctrl_meas.
+    ///
+    /// A forced mode sensor stays in sleep mode, restoring forced mode would
+    /// start a conversion.
+    async fn set_config<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        sampling_configuration: SamplingConfiguration,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        if self.calibration_registers.is_none() {
+            return Err(NotCalibrated);
+        }
+        let (config, ctrl_meas, _) = match sampling_configuration.sensor_mode() {
+            SensorMode::Forced => sampling_configuration
+                .clone()
+                .with_sensor_mode(SensorMode::Sleep)
+                .to_low_level_configuration(),
+            _ => sampling_configuration.to_low_level_configuration(),
+        };
+        self.write_configuration_register(bus, BME280_REGISTER_CONTROL, SensorMode::Sleep as u8)
+            .await?;
+        self.write_configuration_register(bus, BME280_REGISTER_CONFIG, config.into())
+            .await?;
+        self.write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+            .await?;
+        self.sampling_configuration = sampling_configuration;
+        Ok(())
+    }
+
+    /// Triggers a single forced mode conversion and returns its result.
+    ///
+    /// Oversampling is taken from the configuration passed to `setup`. The
+    /// chip goes back to sleep mode once the conversion is done, so the
+    /// sensor draws no measurement current between calls. A sensor in normal
+    /// mode is put to sleep for the conversion and resumes normal mode
+    /// afterwards.
+    pub async fn measure_once<BUS>(&mut self, bus: &mut BUS) -> Result<BME280Response, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        self.convert_forced(bus).await?;
+        self.read(bus).await
+    }
+
+    /// Integer counterpart of [`measure_once`](Self::measure_once).
+    pub async fn measure_once_integer<BUS>(
+        &mut self,
+        bus: &mut BUS,
+    ) -> Result<BME280IntegerResponse, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        self.convert_forced(bus).await?;
+        self.read_integer(bus).await
+    }
+
+    async fn convert_forced<BUS>(&mut self, bus: &mut BUS) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        if self.calibration_registers.is_none() {
+            return Err(NotCalibrated);
+        }
+        // Normal mode is only left through sleep mode.
+        let normal = self.sampling_configuration.sensor_mode() == SensorMode::Normal;
+        if normal {
+            let (_, ctrl_meas, _) = self
+                .sampling_configuration
+                .clone()
+                .with_sensor_mode(SensorMode::Sleep)
+                .to_low_level_configuration();
+            self.write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+                .await?;
+        }
+        let (_, ctrl_meas, _) = self
+            .sampling_configuration
+            .clone()
+            .with_sensor_mode(SensorMode::Forced)
+            .to_low_level_configuration();
+        let conversion = match self
+            .write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+            .await
+        {
+            Ok(()) => self.wait_for_conversion(bus).await,
+            Err(error) => Err(error),
+        };
+        if normal {
+            let (_, ctrl_meas, _) = self.sampling_configuration.to_low_level_configuration();
+            self.write_configuration_register(bus, BME280_REGISTER_CONTROL, ctrl_meas.into())
+                .await?;
+        }
+        conversion
+    }
+
+    async fn wait_for_conversion<BUS>(&mut self, bus: &mut BUS) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let conversion = with_timeout(Duration::from_secs(1), async {
+            Timer::after(self.sampling_configuration.typical_measurement_time()).await;
+            while self.is_measuring(bus).await? {
+                Timer::after(Duration::from_millis(1)).await;
+            }
+            Ok::<(), BME280Error>(())
+        })
+        .await;
+        match conversion {
+            Ok(result) => result,
+            Err(_) => Err(BME280Error::Timeout),
+        }
+    }
+
+    pub async fn read<BUS>(&mut self, bus: &mut BUS) -> Result<BME280Response, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let raw = self.read_raw(bus).await?;
+
+        if let Some(cr) = &self.calibration_registers {
+            let mut response = cr.compensate(&raw, self.compensation);
+            if let Some(sea_level_pressure) = self.sea_level_pressure {
+                response.altitude = response
+                    .pressure
+                    .map(|pressure| altitude(pressure, sea_level_pressure));
+            }
+            Ok(response)
+        } else {
+            Err(NotCalibrated)
+        }
+    }
+
+    /// Reads a measurement without any floating point arithmetic.
+    ///
+    /// Always uses the integer formulas; with [`Compensation::FloatingPoint`]
+    /// selected the 64-bit pressure formula is used.
+    pub async fn read_integer<BUS>(
+        &mut self,
+        bus: &mut BUS,
+    ) -> Result<BME280IntegerResponse, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let raw = self.read_raw(bus).await?;
+
+        if let Some(cr) = &self.calibration_registers {
+            Ok(cr.compensate_integer(&raw, self.compensation))
+        } else {
+            Err(NotCalibrated)
+        }
+    }
+
+    /// Reads the uncompensated ADC values.
+    ///
+    /// They can be compensated later with the coefficients from
+    /// [`calibration_registers`](Self::calibration_registers). Humidity is
+    /// reported as skipped on a BMP280 and before `setup` detected the chip.
+    pub async fn read_raw<BUS>(&mut self, bus: &mut BUS) -> Result<BME280RawResponse, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let mut data: [u8; BME280_REGISTER_DATA_LENGTH] = [0; BME280_REGISTER_DATA_LENGTH];
+        let length = if self.has_humidity() {
+            BME280_REGISTER_DATA_LENGTH
+        } else {
+            data[6..].copy_from_slice(&BME280_ADC_H_SKIPPED.to_be_bytes());
+            BMP280_REGISTER_DATA_LENGTH
+        };
+        self.read_registers_bulk(bus, BME280_REGISTER_DATA_START, &mut data[..length])
+            .await?;
+
+        Ok(data.into())
+    }
+
+    /// Reads the configuration registers 0xF2..=0xF5 back from the chip.
+    ///
+    /// Comparing the result with
+    /// [`sampling_configuration`](Self::sampling_configuration) shows
+    /// whether the writes landed, or whether the chip was reset by a brownout
+    /// and needs `setup` again. In forced mode the chip reports
+    /// [`SensorMode::Sleep`] once the conversion has finished, which
+    /// [`SamplingConfiguration::matches_read_back`] takes into account.
+    pub async fn read_configuration<BUS>(
+        &mut self,
+        bus: &mut BUS,
+    ) -> Result<SamplingConfiguration, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let mut data = [0; 4];
+        self.read_registers_bulk(bus, BME280_REGISTER_CONTROLHUMID, &mut data)
+            .await?;
+        let [ctrl_hum, _status, ctrl_meas, config] = data;
+        // The BMP280 has no ctrl_hum register.
+        let ctrl_hum = if self.has_humidity() { ctrl_hum } else { 0 };
+
+        Ok((
+            Config::from(config),
+            ControlMeasurement::from(ctrl_meas),
+            ControlHumidity::from(ctrl_hum),
+        )
+            .into())
+    }
+
+    /// Configuration applied by `setup`.
+    pub fn sampling_configuration(&self) -> &SamplingConfiguration {
+        &self.sampling_configuration
+    }
+
+    /// Calibration coefficients read by `setup`.
+    pub fn calibration_registers(&self) -> Option<&CalibrationRegisters> {
+        self.calibration_registers.as_ref()
+    }
+
+    async fn read_register_u8<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        register: u8,
+    ) -> Result<u8, BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let mut buf = [0u8; 1];
+        self.interface
+            .read_registers(bus, register, &mut buf)
+            .await?;
+        Ok(buf[0])
+    }
+
+    async fn write_register_8u<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        register: u8,
+        data: u8,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        self.interface.write_register(bus, register, data).await
+    }
+
+    /// Writes a configuration register, verifying the write when enabled
+    /// with [`with_write_verification`](Self::with_write_verification).
+    async fn write_configuration_register<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        register: u8,
+        data: u8,
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        if !self.verify_writes {
+            return self.write_register_8u(bus, register, data).await;
+        }
+        if register == BME280_REGISTER_CONTROL && is_forced_mode(data) {
+            return self.write_forced_mode(bus, data).await;
+        }
+        let mask = match register {
+            BME280_REGISTER_CONTROLHUMID => BME280_CONTROLHUMID_MASK,
+            BME280_REGISTER_CONFIG => BME280_CONFIG_MASK,
+            _ => 0xFF,
+        };
+        for _ in 0..BME280_CONFIGURATION_WRITE_ATTEMPTS {
+            self.write_register_8u(bus, register, data).await?;
+            let read_back = self.read_register_u8(bus, register).await?;
+            if read_back & mask == data & mask {
+                return Ok(());
+            }
+        }
+        Err(BME280Error::ConfigurationNotAccepted)
+    }
+
+    /// Verified write of a forced mode ctrl_meas value.
+    ///
+    /// A forced conversion falls back to sleep mode by itself, so the mode
+    /// bits may already read back as sleep. The write counts as accepted when
+    /// the oversampling bits match and a conversion started: ctrl_meas still
+    /// reports forced mode, the status register reports `measuring`, or
+    /// ctrl_meas differs from its value before the write.
+    async fn write_forced_mode<BUS>(&mut self, bus: &mut BUS, data: u8) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        let previous = self.read_register_u8(bus, BME280_REGISTER_CONTROL).await?;
+        for _ in 0..BME280_CONFIGURATION_WRITE_ATTEMPTS {
+            self.write_register_8u(bus, BME280_REGISTER_CONTROL, data)
+                .await?;
+            let mut read_back = [0u8; 2];
+            self.read_registers_bulk(bus, BME280_REGISTER_STATUS, &mut read_back)
+                .await?;
+            let [status, ctrl_meas] = read_back;
+            let started = is_forced_mode(ctrl_meas)
+                || (status & BME280_STATUS_MEASURING) != 0
+                || ctrl_meas != previous;
+            if started
+                && ctrl_meas & BME280_CONTROL_OVERSAMPLING_MASK
+                    == data & BME280_CONTROL_OVERSAMPLING_MASK
+            {
+                return Ok(());
+            }
+        }
+        Err(BME280Error::ConfigurationNotAccepted)
+    }
+
+    async fn read_registers_bulk<BUS>(
+        &mut self,
+        bus: &mut BUS,
+        register: u8,
+        read: &mut [u8],
+    ) -> Result<(), BME280Error>
+    where
+        IF: Interface<BUS>,
+    {
+        self.interface.read_registers(bus, register, read).await
+    }
+}
+
+fn is_forced_mode(ctrl_meas: u8) -> bool {
+    matches!(
+        SensorMode::try_from(ctrl_meas & 0b11),
+        Ok(SensorMode::Forced)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{
+        reference_configuration, Simulator, REFERENCE_ADC_H, REFERENCE_ADC_P, REFERENCE_ADC_T,
+        REFERENCE_CALIBRATION, SIMULATOR_ADDRESS,
+    };
+    use crate::units::Celsius;
+    use embassy_futures::block_on;
+    use embedded_hal_async::i2c::{ErrorKind, NoAcknowledgeSource};
+
+    fn configuration() -> SamplingConfiguration {
+        reference_configuration()
+            .with_sensor_mode(SensorMode::Normal)
+            .with_standby_duration(StandbyDuration::Millis1000)
+            .with_filter(Filter::X8)
+    }
+
+    #[test]
+    fn setup_resets_and_configures_the_chip() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        assert_eq!(bus.resets, 1);
+        assert_eq!(bus.register(BME280_REGISTER_CONTROLHUMID), 0b001);
+        // t_sb = 1000 ms, filter = 8
+        assert_eq!(bus.register(BME280_REGISTER_CONFIG), 0b1010_1100);
+        // osrs_t = x1, osrs_p = x1, mode = normal
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0111);
+        // ctrl_hum only takes effect after a ctrl_meas write.
+        let ctrl_hum = bus
+            .writes
+            .iter()
+            .rposition(|w| w.0 == BME280_REGISTER_CONTROLHUMID);
+        let ctrl_meas = bus
+            .writes
+            .iter()
+            .rposition(|w| w.0 == BME280_REGISTER_CONTROL);
+        assert!(ctrl_hum < ctrl_meas);
+    }
+
+    #[test]
+    fn reconfigure_skips_reset() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.writes.clear();
+
+        let configuration = configuration()
+            .with_humidity_oversampling(Oversampling::X4)
+            .with_sensor_mode(SensorMode::Forced);
+        block_on(sensor.reconfigure(&mut bus, configuration.clone())).unwrap();
+
+        assert_eq!(bus.resets, 1);
+        assert_eq!(
+            bus.writes,
+            [
+                (BME280_REGISTER_CONTROL, 0),
+                (BME280_REGISTER_CONTROLHUMID, 0b011),
+                (BME280_REGISTER_CONFIG, 0b1010_1100),
+                (BME280_REGISTER_CONTROL, 0b0010_0101),
+            ]
+        );
+        assert_eq!(sensor.sampling_configuration(), &configuration);
+    }
+
+    #[test]
+    fn dropped_write_goes_unnoticed_without_verification() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.dropped_writes = 1;
+
+        block_on(sensor.set_sensor_mode(&mut bus, SensorMode::Sleep)).unwrap();
+
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL) & 0b11, 0b11);
+    }
+
+    #[test]
+    fn verified_write_is_repeated() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.writes.clear();
+        bus.dropped_writes = 2;
+
+        let configuration = configuration().with_filter(Filter::Off);
+        block_on(sensor.reconfigure(&mut bus, configuration.clone())).unwrap();
+
+        assert_eq!(
+            block_on(sensor.read_configuration(&mut bus)).unwrap(),
+            configuration
+        );
+        // The sleep mode write is dropped twice and accepted the third time.
+        assert_eq!(&bus.writes[..3], [(BME280_REGISTER_CONTROL, 0); 3]);
+    }
+
+    #[test]
+    fn verified_forced_mode_write_ignores_mode_bits() {
+        let mut bus = Simulator::new();
+        bus.conversion_reads = 0;
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+
+        let configuration = configuration().with_sensor_mode(SensorMode::Forced);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+
+        // The conversion finished before the read back.
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0100);
+    }
+
+    #[test]
+    fn verified_forced_mode_write_retries_a_lost_write() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+        let configuration = configuration().with_sensor_mode(SensorMode::Sleep);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        bus.writes.clear();
+        bus.dropped_writes = 1;
+
+        block_on(sensor.set_sensor_mode(&mut bus, SensorMode::Forced)).unwrap();
+
+        // The lost write left ctrl_meas in sleep mode with nothing measuring.
+        assert_eq!(
+            bus.writes,
+            [
+                (BME280_REGISTER_CONTROL, 0b0010_0101),
+                (BME280_REGISTER_CONTROL, 0b0010_0101)
+            ]
+        );
+        assert_eq!(
+            sensor.sampling_configuration().sensor_mode(),
+            SensorMode::Forced
+        );
+    }
+
+    #[test]
+    fn verified_forced_mode_write_reports_lost_writes() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+        let configuration = configuration().with_sensor_mode(SensorMode::Sleep);
+        block_on(sensor.setup(&mut bus, configuration.clone())).unwrap();
+        bus.dropped_writes = usize::MAX;
+
+        let result = block_on(sensor.set_sensor_mode(&mut bus, SensorMode::Forced));
+
+        assert_eq!(result, Err(BME280Error::ConfigurationNotAccepted));
+        assert_eq!(sensor.sampling_configuration(), &configuration);
+    }
+
+    #[test]
+    fn verified_measure_once_retries_a_lost_trigger() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+        let configuration = configuration().with_sensor_mode(SensorMode::Sleep);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        bus.writes.clear();
+        bus.dropped_writes = 1;
+
+        let response = block_on(sensor.measure_once(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(
+            bus.writes,
+            [
+                (BME280_REGISTER_CONTROL, 0b0010_0101),
+                (BME280_REGISTER_CONTROL, 0b0010_0101)
+            ]
+        );
+        assert_eq!(bus.conversions, 2);
+    }
+
+    #[test]
+    fn verified_measure_once_reports_a_lost_trigger() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+        let configuration = configuration().with_sensor_mode(SensorMode::Sleep);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        bus.dropped_writes = usize::MAX;
+
+        let result = block_on(sensor.measure_once(&mut bus));
+
+        assert_eq!(result.err(), Some(BME280Error::ConfigurationNotAccepted));
+        assert_eq!(bus.conversions, 1);
+    }
+
+    #[test]
+    fn set_sensor_mode_leaves_normal_mode_through_sleep_mode() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.writes.clear();
+
+        block_on(sensor.set_sensor_mode(&mut bus, SensorMode::Forced)).unwrap();
+
+        assert_eq!(
+            bus.writes,
+            [
+                (BME280_REGISTER_CONTROL, 0b0010_0100),
+                (BME280_REGISTER_CONTROL, 0b0010_0101)
+            ]
+        );
+        assert_eq!(
+            sensor.sampling_configuration().sensor_mode(),
+            SensorMode::Forced
+        );
+    }
+
+    #[test]
+    fn rejected_configuration_is_reported() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS).with_write_verification(true);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.dropped_writes = usize::MAX;
+
+        let result = block_on(sensor.set_filter(&mut bus, Filter::Off));
+
+        assert_eq!(result, Err(BME280Error::ConfigurationNotAccepted));
+        assert_eq!(sensor.sampling_configuration(), &configuration());
+    }
+
+    #[test]
+    fn read_configuration_matches_setup() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let configuration = block_on(sensor.read_configuration(&mut bus)).unwrap();
+
+        assert_eq!(&configuration, sensor.sampling_configuration());
+    }
+
+    #[test]
+    fn read_configuration_detects_reset_chip() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        for register in 0xF2..=0xF5 {
+            bus.registers[register] = 0;
+        }
+
+        let configuration = block_on(sensor.read_configuration(&mut bus)).unwrap();
+
+        assert_eq!(configuration, SamplingConfiguration::default());
+        assert!(!sensor
+            .sampling_configuration()
+            .matches_read_back(&configuration));
+    }
+
+    #[test]
+    fn read_configuration_in_forced_mode_matches_after_conversion() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        let configuration = configuration().with_sensor_mode(SensorMode::Forced);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        block_on(sensor.measure_once(&mut bus)).unwrap();
+
+        let read_back = block_on(sensor.read_configuration(&mut bus)).unwrap();
+
+        assert_eq!(read_back.sensor_mode(), SensorMode::Sleep);
+        assert!(sensor
+            .sampling_configuration()
+            .matches_read_back(&read_back));
+    }
+
+    #[test]
+    fn read_configuration_on_bmp280_skips_humidity() {
+        let mut bus = Simulator::new().with_chip_id(0x58);
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.registers[BME280_REGISTER_CONTROLHUMID as usize] = 0xFF;
+
+        let configuration = block_on(sensor.read_configuration(&mut bus)).unwrap();
+
+        assert_eq!(&configuration, sensor.sampling_configuration());
+    }
+
+    #[test]
+    fn reconfigure_keeps_spi3w() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration().with_spi3w(true))).unwrap();
+
+        block_on(sensor.reconfigure(&mut bus, configuration())).unwrap();
+
+        assert_eq!(bus.register(BME280_REGISTER_CONFIG), 0b1010_1101);
+    }
+
+    #[test]
+    fn reconfigure_before_setup_is_not_calibrated() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.reconfigure(&mut bus, configuration()));
+
+        assert_eq!(result, Err(BME280Error::NotCalibrated));
+        assert!(bus.writes.is_empty());
+    }
+
+    #[test]
+    fn set_sensor_mode_writes_ctrl_meas_only() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.writes.clear();
+
+        block_on(sensor.set_sensor_mode(&mut bus, SensorMode::Sleep)).unwrap();
+
+        assert_eq!(bus.writes, [(BME280_REGISTER_CONTROL, 0b0010_0100)]);
+        assert_eq!(
+            sensor.sampling_configuration().sensor_mode(),
+            SensorMode::Sleep
+        );
+    }
+
+    #[test]
+    fn set_filter_writes_config_in_sleep_mode() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.writes.clear();
+
+        block_on(sensor.set_filter(&mut bus, Filter::Off)).unwrap();
+        block_on(sensor.set_standby_duration(&mut bus, StandbyDuration::Millis0_5)).unwrap();
+
+        assert_eq!(
+            bus.writes,
+            [
+                (BME280_REGISTER_CONTROL, 0),
+                (BME280_REGISTER_CONFIG, 0b1010_0000),
+                (BME280_REGISTER_CONTROL, 0b0010_0111),
+                (BME280_REGISTER_CONTROL, 0),
+                (BME280_REGISTER_CONFIG, 0b0000_0000),
+                (BME280_REGISTER_CONTROL, 0b0010_0111),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_filter_in_forced_mode_starts_no_conversion() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        let configuration = configuration().with_sensor_mode(SensorMode::Forced);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        bus.writes.clear();
+        let conversions = bus.conversions;
+
+        block_on(sensor.set_filter(&mut bus, Filter::X16)).unwrap();
+        block_on(sensor.set_standby_duration(&mut bus, StandbyDuration::Millis0_5)).unwrap();
+
+        assert!(!bus
+            .writes
+            .iter()
+            .any(|&(register, value)| register == BME280_REGISTER_CONTROL && value & 0b11 != 0));
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0100);
+        assert_eq!(bus.conversions, conversions);
+        assert_eq!(
+            sensor.sampling_configuration().sensor_mode(),
+            SensorMode::Forced
+        );
+        assert!(block_on(sensor.measure_once(&mut bus)).is_ok());
+    }
+
+    #[test]
+    fn setup_waits_for_nvm_copy() {
+        let mut bus = Simulator::new();
+        bus.nvm_copy_reads = 3;
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+    }
+
+    #[test]
+    fn setup_times_out_when_nvm_copy_never_ends() {
+        let mut bus = Simulator::new();
+        bus.nvm_copy_reads = usize::MAX;
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.setup(&mut bus, configuration()));
+
+        assert!(matches!(result, Err(BME280Error::Timeout)));
+    }
+
+    #[test]
+    fn setup_rejects_unknown_chip_id() {
+        let mut bus = Simulator::new().with_chip_id(0x42);
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.setup(&mut bus, configuration()));
+
+        assert!(matches!(result, Err(BME280Error::InvalidChipId(0x42))));
+        assert_eq!(bus.resets, 0);
+    }
+
+    #[test]
+    fn setup_reports_bus_errors() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(0x77);
+
+        let result = block_on(sensor.setup(&mut bus, configuration()));
+
+        assert_eq!(
+            result,
+            Err(BME280Error::I2CError(ErrorKind::NoAcknowledge(
+                NoAcknowledgeSource::Address
+            )))
+        );
+    }
+
+    #[test]
+    fn read_compensates_reference_values() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert!((response.pressure.unwrap().0 - 100_653.25).abs() < 0.01);
+        assert!((response.humidity.unwrap().0 - 55.876).abs() < 0.001);
+    }
+
+    #[test]
+    fn read_before_setup_is_not_calibrated() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.read(&mut bus));
+
+        assert!(matches!(result, Err(BME280Error::NotCalibrated)));
+    }
+
+    #[test]
+    fn read_reports_bus_errors() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.fault = Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data));
+
+        let result = block_on(sensor.read(&mut bus));
+
+        assert!(matches!(
+            result,
+            Err(BME280Error::I2CError(ErrorKind::NoAcknowledge(
+                NoAcknowledgeSource::Data
+            )))
+        ));
+    }
+
+    #[test]
+    fn measure_once_returns_a_fresh_conversion() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        let configuration = configuration().with_sensor_mode(SensorMode::Forced);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        bus.set_adc(415_148, 530_000, 30_000);
+
+        let stale = block_on(sensor.read(&mut bus)).unwrap();
+        let fresh = block_on(sensor.measure_once(&mut bus)).unwrap();
+
+        assert!(fresh.temperature > stale.temperature);
+        // osrs_t = x1, osrs_p = x1, mode = sleep after the conversion
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0100);
+        assert_eq!(
+            bus.writes.last(),
+            Some(&(BME280_REGISTER_CONTROL, 0b0010_0101))
+        );
+    }
+
+    #[test]
+    fn measure_once_in_normal_mode_resumes_normal_mode() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.writes.clear();
+
+        block_on(sensor.measure_once(&mut bus)).unwrap();
+
+        // sleep, forced, then normal mode again
+        assert_eq!(
+            bus.writes,
+            [
+                (BME280_REGISTER_CONTROL, 0b0010_0100),
+                (BME280_REGISTER_CONTROL, 0b0010_0101),
+                (BME280_REGISTER_CONTROL, 0b0010_0111),
+            ]
+        );
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0111);
+        assert_eq!(
+            block_on(sensor.read_configuration(&mut bus)).unwrap(),
+            configuration()
+        );
+    }
+
+    #[test]
+    fn measure_once_before_setup_is_not_calibrated() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.measure_once(&mut bus));
+
+        assert!(matches!(result, Err(BME280Error::NotCalibrated)));
+        assert!(bus.writes.is_empty());
+    }
+
+    #[test]
+    fn measure_once_times_out_when_conversion_never_ends() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.conversion_reads = usize::MAX;
+
+        let result = block_on(sensor.measure_once(&mut bus));
+
+        assert!(matches!(result, Err(BME280Error::Timeout)));
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0111);
+    }
+
+    #[test]
+    fn setup_detects_bmp280_without_humidity() {
+        let mut bus = Simulator::new().with_chip_id(0x58);
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(sensor.chip_model(), Some(ChipModel::BMP280));
+        assert!(!bus
+            .writes
+            .iter()
+            .any(|w| w.0 == BME280_REGISTER_CONTROLHUMID));
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert!((response.pressure.unwrap().0 - 100_653.25).abs() < 0.01);
+        assert!(response.humidity.is_none());
+    }
+
+    #[test]
+    fn setup_detects_bme280() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        assert_eq!(sensor.chip_model(), Some(ChipModel::BME280));
+    }
+
+    #[test]
+    fn read_reports_skipped_channels_as_absent() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        let configuration = configuration()
+            .with_pressure_oversampling(Oversampling::Skip)
+            .with_humidity_oversampling(Oversampling::Skip);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        bus.set_adc(0x80000, REFERENCE_ADC_T, 0x8000);
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert!(response.pressure.is_none());
+        assert!(response.humidity.is_none());
+    }
+
+    #[test]
+    fn read_without_temperature_has_no_channels() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        bus.set_adc(REFERENCE_ADC_P, 0x80000, REFERENCE_ADC_H);
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert!(response.temperature.is_none());
+        assert!(response.pressure.is_none());
+        assert!(response.humidity.is_none());
+    }
+
+    #[test]
+    fn read_with_floating_point_compensation() {
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_compensation(Compensation::FloatingPoint);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert!((response.temperature.unwrap().0 - 25.0825).abs() < 0.0001);
+        assert!((response.pressure.unwrap().0 - 100_653.27).abs() < 0.01);
+        assert!((response.humidity.unwrap().0 - 55.8768).abs() < 0.0001);
+    }
+
+    #[test]
+    fn read_with_32_bit_pressure_compensation() {
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_compensation(Compensation::Integer32);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(response.pressure, Some(Pascal(100_656.0)));
+    }
+
+    #[test]
+    fn read_reports_altitude_with_sea_level_pressure() {
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_sea_level_pressure(Pascal(101_325.0));
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        // 100653 Pa is about 56 m above the standard sea-level pressure.
+        assert!((response.altitude.unwrap().0 - 56.1).abs() < 0.5);
+    }
+
+    #[test]
+    fn read_without_sea_level_pressure_has_no_altitude() {
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_sea_level_pressure(Pascal(101_325.0));
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        sensor.set_sea_level_pressure(None);
+
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert!(response.pressure.is_some());
+        assert!(response.altitude.is_none());
+    }
+
+    #[test]
+    fn read_integer_returns_fixed_point_values() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let response = block_on(sensor.read_integer(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(2508));
+        assert_eq!(response.humidity, Some(57_217));
+        assert_eq!(response.pressure, Some(25_767_233));
+    }
+
+    #[test]
+    fn read_integer_with_32_bit_pressure_compensation_is_q24_8() {
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_compensation(Compensation::Integer32);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let response = block_on(sensor.read_integer(&mut bus)).unwrap();
+
+        assert_eq!(response.pressure, Some(100_656 << 8));
+    }
+
+    #[test]
+    fn read_raw_returns_adc_values() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let raw = block_on(sensor.read_raw(&mut bus)).unwrap();
+
+        assert_eq!(raw.pressure, REFERENCE_ADC_P);
+        assert_eq!(raw.temperature, REFERENCE_ADC_T);
+        assert_eq!(raw.humidity, REFERENCE_ADC_H);
+        let cr = sensor.calibration_registers().unwrap();
+        let response = cr.compensate(&raw, Compensation::Integer);
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+    }
+
+    #[test]
+    fn read_raw_on_bmp280_reports_humidity_as_skipped() {
+        let mut bus = Simulator::new().with_chip_id(0x58);
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        let raw = block_on(sensor.read_raw(&mut bus)).unwrap();
+
+        assert_eq!(raw.humidity, 0x8000);
+    }
+
+    #[test]
+    fn setup_with_saved_calibration_skips_reset() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        let saved = sensor.calibration_registers().unwrap().to_bytes();
+
+        let mut bus = Simulator::new();
+        bus.set_calibration([0; 33]);
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_calibration_registers(saved.into());
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(bus.resets, 0);
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(bus.register(BME280_REGISTER_CONTROL), 0b0010_0111);
+    }
+
+    #[test]
+    fn setup_again_resets_the_chip() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        let saved = sensor.calibration_registers().unwrap().to_bytes();
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        assert_eq!(bus.resets, 2);
+
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_calibration_registers(saved.into());
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        assert_eq!(bus.resets, 1);
+    }
+
+    #[test]
+    fn setup_retries_corrupted_calibration_read() {
+        let mut bus = Simulator::new();
+        bus.calibration_faults = 1;
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+    }
+
+    #[test]
+    fn setup_rejects_persistently_corrupted_calibration() {
+        let mut bus = Simulator::new();
+        bus.calibration_faults = usize::MAX;
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.setup(&mut bus, configuration()));
+
+        assert!(matches!(result, Err(BME280Error::InvalidCalibration)));
+        assert!(sensor.calibration_registers().is_none());
+    }
+
+    #[test]
+    fn setup_rejects_blank_calibration() {
+        let mut bus = Simulator::new();
+        bus.set_calibration([0; 33]);
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+
+        let result = block_on(sensor.setup(&mut bus, configuration()));
+
+        assert!(matches!(result, Err(BME280Error::InvalidCalibration)));
+    }
+
+    #[test]
+    fn setup_reads_calibration_when_saved_one_is_corrupted() {
+        let mut bus = Simulator::new();
+        let mut sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_calibration_registers([0xFF; 33].into());
+
+        block_on(sensor.setup(&mut bus, configuration())).unwrap();
+
+        assert_eq!(bus.resets, 1);
+        assert_eq!(
+            sensor.calibration_registers().unwrap().to_bytes(),
+            REFERENCE_CALIBRATION
+        );
+    }
+}
@@ -1,5 +1,36 @@
-use crate::{BME280_REGISTER_DIG_FIRST_LENGTH, BME280_REGISTER_DIG_SECOND_LENGTH};
+use crate::units::{Celsius, Pascal, RelativeHumidity};
+use crate::{
+    BME280IntegerResponse, BME280RawResponse, BME280Response, ChipModel, BME280_ADC_H_SKIPPED,
+    BME280_ADC_SKIPPED, BME280_REGISTER_DIG_FIRST_LENGTH, BME280_REGISTER_DIG_SECOND_LENGTH,
+};
 
+/// Compensation formulas used to turn raw ADC values into measurements.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Compensation {
+    /// Bosch 32-bit integer formulas for temperature and humidity and the
+    /// 64-bit integer formula for pressure.
+    #[default]
+    Integer,
+    /// Bosch 32-bit integer formulas throughout, using
+    /// [`compensate_pressure_32`](CalibrationRegisters::compensate_pressure_32)
+    /// for pressure. Avoids 64-bit arithmetic, which is slow on Cortex-M0+,
+    /// at the cost of a 1 Pa pressure resolution and a deviation of a few Pa
+    /// (below 0.1 hPa) from the 64-bit result.
+    Integer32,
+    /// Bosch double precision floating point formulas. Finer resolution,
+    /// at the cost of soft-float code on MCUs without an FPU.
+    FloatingPoint,
+}
+
+/// Trimming coefficients stored in the chip NVM.
+///
+/// They never change for a given chip, so they can be saved (as the raw
+/// register bytes from [`to_bytes`](Self::to_bytes), or through serde with
+/// the `serde` feature) and handed back to
+/// [`BME280Sensor::with_calibration_registers`](crate::bme280::BME280Sensor::with_calibration_registers)
+/// after a deep sleep.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CalibrationRegisters {
     pub dig_t1: u16,
     pub dig_t2: i16,
@@ -43,14 +74,128 @@ impl From<[u8; BME280_REGISTER_DIG_FIRST_LENGTH + BME280_REGISTER_DIG_SECOND_LEN
             dig_h1: value[25],
             dig_h2: i16::from_le_bytes([value[26], value[27]]),
             dig_h3: value[28],
-            dig_h4: i16::from(value[29]) << 4 | i16::from(value[30]) & 0xf,
-            dig_h5: ((i16::from(value[30]) & 0xf0) >> 4) | (i16::from(value[31]) << 4),
+            dig_h4: i16::from(value[29] as i8) << 4 | i16::from(value[30]) & 0xf,
+            dig_h5: ((i16::from(value[30]) & 0xf0) >> 4) | (i16::from(value[31] as i8) << 4),
             dig_h6: value[32] as i8,
         }
     }
 }
 
+impl From<&CalibrationRegisters>
+    for [u8; BME280_REGISTER_DIG_FIRST_LENGTH + BME280_REGISTER_DIG_SECOND_LENGTH]
+{
+    fn from(cr: &CalibrationRegisters) -> Self {
+        let mut value = [0u8; BME280_REGISTER_DIG_FIRST_LENGTH + BME280_REGISTER_DIG_SECOND_LENGTH];
+        value[0..2].copy_from_slice(&cr.dig_t1.to_le_bytes());
+        value[2..4].copy_from_slice(&cr.dig_t2.to_le_bytes());
+        value[4..6].copy_from_slice(&cr.dig_t3.to_le_bytes());
+        value[6..8].copy_from_slice(&cr.dig_p1.to_le_bytes());
+        value[8..10].copy_from_slice(&cr.dig_p2.to_le_bytes());
+        value[10..12].copy_from_slice(&cr.dig_p3.to_le_bytes());
+        value[12..14].copy_from_slice(&cr.dig_p4.to_le_bytes());
+        value[14..16].copy_from_slice(&cr.dig_p5.to_le_bytes());
+        value[16..18].copy_from_slice(&cr.dig_p6.to_le_bytes());
+        value[18..20].copy_from_slice(&cr.dig_p7.to_le_bytes());
+        value[20..22].copy_from_slice(&cr.dig_p8.to_le_bytes());
+        value[22..24].copy_from_slice(&cr.dig_p9.to_le_bytes());
+        value[25] = cr.dig_h1;
+        value[26..28].copy_from_slice(&cr.dig_h2.to_le_bytes());
+        value[28] = cr.dig_h3;
+        value[29] = (cr.dig_h4 >> 4) as u8;
+        value[30] = (cr.dig_h4 & 0xf) as u8 | ((cr.dig_h5 & 0xf) << 4) as u8;
+        value[31] = (cr.dig_h5 >> 4) as u8;
+        value[32] = cr.dig_h6 as u8;
+        value
+    }
+}
+
 impl CalibrationRegisters {
+    /// Size of the raw coefficient block: 26 bytes from 0x88 followed by
+    /// 7 bytes from 0xE1.
+    pub const LENGTH: usize = BME280_REGISTER_DIG_FIRST_LENGTH + BME280_REGISTER_DIG_SECOND_LENGTH;
+
+    /// Sanity check of the coefficients, catching blocks read as all 0x00
+    /// or all 0xFF from a misbehaving bus.
+    ///
+    /// dig_T1 and dig_P1 must be neither 0 nor 0xFFFF (a zero dig_P1 makes
+    /// pressure compensation return 0), and dig_T2 and dig_P2 neither 0 nor
+    /// -1. On a BME280 the same holds for dig_H2.
+    pub fn is_plausible(&self, chip_model: ChipModel) -> bool {
+        let unsigned = |value: u16| value != 0 && value != u16::MAX;
+        let signed = |value: i16| value != 0 && value != -1;
+
+        unsigned(self.dig_t1)
+            && unsigned(self.dig_p1)
+            && signed(self.dig_t2)
+            && signed(self.dig_p2)
+            && (!chip_model.has_humidity() || signed(self.dig_h2))
+    }
+
+    /// Raw register layout, the inverse of `From<[u8; 33]>`.
+    /// The reserved byte at 0xA0 is stored as zero.
+    pub fn to_bytes(&self) -> [u8; Self::LENGTH] {
+        self.into()
+    }
+
+    /// Compensates a raw reading with the selected formulas.
+    pub fn compensate(
+        &self,
+        raw: &BME280RawResponse,
+        compensation: Compensation,
+    ) -> BME280Response {
+        if compensation != Compensation::FloatingPoint {
+            return self.compensate_integer(raw, compensation).into();
+        }
+
+        // Pressure and humidity compensation need t_fine, so skipping
+        // temperature leaves every channel empty.
+        let t_fine = (raw.temperature != BME280_ADC_SKIPPED)
+            .then(|| self.compensate_temperature_f64(raw.temperature as i32));
+        let temperature = t_fine.map(|t_fine| Celsius((t_fine / 5120.0) as f32));
+        let humidity = t_fine
+            .filter(|_| raw.humidity != BME280_ADC_H_SKIPPED)
+            .map(|t_fine| {
+                RelativeHumidity(self.compensate_humidity_f64(raw.humidity, t_fine) as f32)
+            });
+        let pressure = t_fine
+            .filter(|_| raw.pressure != BME280_ADC_SKIPPED)
+            .map(|t_fine| Pascal(self.compensate_pressure_f64(raw.pressure, t_fine) as f32));
+
+        BME280Response {
+            temperature,
+            humidity,
+            pressure,
+            altitude: None,
+        }
+    }
+
+    /// Compensates a raw reading with the integer formulas only;
+    /// [`Compensation::FloatingPoint`] falls back to [`Compensation::Integer`].
+    pub fn compensate_integer(
+        &self,
+        raw: &BME280RawResponse,
+        compensation: Compensation,
+    ) -> BME280IntegerResponse {
+        let t_fine = (raw.temperature != BME280_ADC_SKIPPED)
+            .then(|| self.compensate_temperature(raw.temperature as i32));
+        let temperature = t_fine.map(|t_fine| (t_fine * 5 + 128) >> 8);
+        let humidity = t_fine
+            .filter(|_| raw.humidity != BME280_ADC_H_SKIPPED)
+            .map(|t_fine| self.compensate_humidity(raw.humidity, t_fine));
+        let pressure = t_fine
+            .filter(|_| raw.pressure != BME280_ADC_SKIPPED)
+            .map(|t_fine| match compensation {
+                Compensation::Integer32 => self.compensate_pressure_32(raw.pressure, t_fine) << 8,
+                _ => self.compensate_pressure(raw.pressure, t_fine),
+            });
+
+        BME280IntegerResponse {
+            temperature,
+            humidity,
+            pressure,
+        }
+    }
+
     pub fn compensate_temperature(&self, adc_t: i32) -> i32 {
         let var1 = (((adc_t >> 3) - (i32::from(self.dig_t1) << 1)) * i32::from(self.dig_t2)) >> 11;
         let var2 = (((((adc_t >> 4) - i32::from(self.dig_t1))
@@ -109,10 +254,214 @@ impl CalibrationRegisters {
             let var2 = (i64::from(self.dig_p8) * var4) >> 19;
             let var5 = ((var4 + var1 + var2) >> 8) + (i64::from(self.dig_p7) << 4);
 
-            let p = var5;
-            let pressure = p as u32;
+            var5 as u32
+        }
+    }
+
+    /// 32-bit variant of [`compensate_pressure`](Self::compensate_pressure).
+    ///
+    /// Returns the pressure in Pa (not Q24.8), with 1 Pa resolution.
+    pub fn compensate_pressure_32(&self, adc_p: u32, t_fine: i32) -> u32 {
+        let var1 = (t_fine >> 1) - 64_000;
+        let var2 = (((var1 >> 2) * (var1 >> 2)) >> 11) * i32::from(self.dig_p6);
+        let var2 = var2 + ((var1 * i32::from(self.dig_p5)) << 1);
+        let var2 = (var2 >> 2) + (i32::from(self.dig_p4) << 16);
+        let var1 = (((i32::from(self.dig_p3) * (((var1 >> 2) * (var1 >> 2)) >> 13)) >> 3)
+            + ((i32::from(self.dig_p2) * var1) >> 1))
+            >> 18;
+        let var1 = ((32_768 + var1) * i32::from(self.dig_p1)) >> 15;
+
+        if var1 == 0 {
+            0
+        } else {
+            let p = ((1_048_576 - adc_p as i32) as u32)
+                .wrapping_sub((var2 >> 12) as u32)
+                .wrapping_mul(3125);
+            let p = if p < 0x8000_0000 {
+                (p << 1) / var1 as u32
+            } else {
+                (p / var1 as u32) * 2
+            };
+            let var1 = (i32::from(self.dig_p9) * (((p >> 3) * (p >> 3)) >> 13) as i32) >> 12;
+            let var2 = ((p >> 2) as i32 * i32::from(self.dig_p8)) >> 13;
+
+            (p as i32 + ((var1 + var2 + i32::from(self.dig_p7)) >> 4)) as u32
+        }
+    }
+
+    /// Floating point counterpart of [`compensate_temperature`](Self::compensate_temperature).
+    ///
+    /// Returns t_fine, the temperature in °C is `t_fine / 5120.0`.
+    pub fn compensate_temperature_f64(&self, adc_t: i32) -> f64 {
+        let adc_t = f64::from(adc_t);
+        let dig_t1 = f64::from(self.dig_t1);
+        let var1 = (adc_t / 16_384.0 - dig_t1 / 1024.0) * f64::from(self.dig_t2);
+        let var2 = (adc_t / 131_072.0 - dig_t1 / 8192.0)
+            * (adc_t / 131_072.0 - dig_t1 / 8192.0)
+            * f64::from(self.dig_t3);
+
+        var1 + var2
+    }
+
+    /// Relative humidity in %.
+    pub fn compensate_humidity_f64(&self, adc_h: u16, t_fine: f64) -> f64 {
+        let var_h = t_fine - 76_800.0;
+        let var_h = (f64::from(adc_h)
+            - (f64::from(self.dig_h4) * 64.0 + f64::from(self.dig_h5) / 16_384.0 * var_h))
+            * (f64::from(self.dig_h2) / 65_536.0
+                * (1.0
+                    + f64::from(self.dig_h6) / 67_108_864.0
+                        * var_h
+                        * (1.0 + f64::from(self.dig_h3) / 67_108_864.0 * var_h)));
+        let var_h = var_h * (1.0 - f64::from(self.dig_h1) * var_h / 524_288.0);
+
+        var_h.clamp(0.0, 100.0)
+    }
+
+    /// Pressure in Pa.
+    pub fn compensate_pressure_f64(&self, adc_p: u32, t_fine: f64) -> f64 {
+        let var1 = t_fine / 2.0 - 64_000.0;
+        let var2 = var1 * var1 * f64::from(self.dig_p6) / 32_768.0;
+        let var2 = var2 + var1 * f64::from(self.dig_p5) * 2.0;
+        let var2 = var2 / 4.0 + f64::from(self.dig_p4) * 65_536.0;
+        let var1 = (f64::from(self.dig_p3) * var1 * var1 / 524_288.0
+            + f64::from(self.dig_p2) * var1)
+            / 524_288.0;
+        let var1 = (1.0 + var1 / 32_768.0) * f64::from(self.dig_p1);
+
+        if var1 == 0.0 {
+            0.0
+        } else {
+            let p = 1_048_576.0 - f64::from(adc_p);
+            let p = (p - var2 / 4096.0) * 6250.0 / var1;
+            let var1 = f64::from(self.dig_p9) * p * p / 2_147_483_648.0;
+            let var2 = p * f64::from(self.dig_p8) / 32_768.0;
+
+            p + (var1 + var2 + f64::from(self.dig_p7)) / 16.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{
+        REFERENCE_ADC_H, REFERENCE_ADC_P, REFERENCE_ADC_T, REFERENCE_CALIBRATION,
+    };
+
+    fn calibration() -> CalibrationRegisters {
+        REFERENCE_CALIBRATION.into()
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let cr = calibration();
+
+        assert_eq!(cr.to_bytes(), REFERENCE_CALIBRATION);
+        assert_eq!(CalibrationRegisters::from(cr.to_bytes()), cr);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let cr = calibration();
+
+        let json = serde_json::to_string(&cr).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<CalibrationRegisters>(&json).unwrap(),
+            cr
+        );
+    }
+
+    #[test]
+    fn bytes_round_trip_with_negative_humidity_coefficients() {
+        let mut cr = calibration();
+        cr.dig_h4 = -300;
+        cr.dig_h5 = -5;
+        cr.dig_h6 = -20;
+
+        assert_eq!(CalibrationRegisters::from(cr.to_bytes()), cr);
+    }
+
+    #[test]
+    fn reference_calibration_is_plausible() {
+        assert!(calibration().is_plausible(ChipModel::BME280));
+    }
+
+    #[test]
+    fn blank_calibration_is_not_plausible() {
+        for byte in [0x00, 0xFF] {
+            let cr = CalibrationRegisters::from([byte; CalibrationRegisters::LENGTH]);
+            assert!(!cr.is_plausible(ChipModel::BME280));
+            assert!(!cr.is_plausible(ChipModel::BMP280));
+        }
+    }
+
+    #[test]
+    fn bmp280_calibration_without_humidity_is_plausible() {
+        let mut bytes = REFERENCE_CALIBRATION;
+        bytes[26..].fill(0);
+        let cr = CalibrationRegisters::from(bytes);
+
+        assert!(cr.is_plausible(ChipModel::BMP280));
+        assert!(!cr.is_plausible(ChipModel::BME280));
+    }
+
+    #[test]
+    fn integer_compensation_matches_reference() {
+        let cr = calibration();
+
+        let t_fine = cr.compensate_temperature(REFERENCE_ADC_T as i32);
+
+        assert_eq!(t_fine, 128_422);
+        assert_eq!((t_fine * 5 + 128) >> 8, 2508);
+        assert_eq!(cr.compensate_pressure(REFERENCE_ADC_P, t_fine), 25_767_233);
+        assert_eq!(cr.compensate_humidity(REFERENCE_ADC_H, t_fine), 57_217);
+    }
+
+    #[test]
+    fn integer_32_pressure_compensation_matches_reference() {
+        let cr = calibration();
+
+        let t_fine = cr.compensate_temperature(REFERENCE_ADC_T as i32);
+
+        assert_eq!(cr.compensate_pressure_32(REFERENCE_ADC_P, t_fine), 100_656);
+    }
+
+    #[test]
+    fn floating_point_compensation_matches_reference() {
+        let cr = calibration();
+
+        let t_fine = cr.compensate_temperature_f64(REFERENCE_ADC_T as i32);
+
+        assert!((t_fine / 5120.0 - 25.0825).abs() < 0.0001);
+        assert!((cr.compensate_pressure_f64(REFERENCE_ADC_P, t_fine) - 100_653.27).abs() < 0.01);
+        assert!((cr.compensate_humidity_f64(REFERENCE_ADC_H, t_fine) - 55.8768).abs() < 0.0001);
+    }
+
+    #[test]
+    fn compensation_backends_agree() {
+        let cr = calibration();
+
+        for adc_t in (400_000..600_000).step_by(10_000) {
+            let t_fine = cr.compensate_temperature(adc_t);
+            let t_fine_f64 = cr.compensate_temperature_f64(adc_t);
+            let temperature = f64::from((t_fine * 5 + 128) >> 8) / 100.0;
+            assert!((temperature - t_fine_f64 / 5120.0).abs() <= 0.01);
 
-            pressure
+            for adc_p in (250_000..500_000).step_by(25_000) {
+                let pressure = f64::from(cr.compensate_pressure(adc_p, t_fine)) / 256.0;
+                let pressure_f64 = cr.compensate_pressure_f64(adc_p, t_fine_f64);
+                assert!((pressure - pressure_f64).abs() < 0.5);
+                let pressure_32 = f64::from(cr.compensate_pressure_32(adc_p, t_fine));
+                assert!((pressure - pressure_32).abs() < 8.0);
+            }
+            for adc_h in (20_000..40_000).step_by(2_000) {
+                let humidity = f64::from(cr.compensate_humidity(adc_h, t_fine)) / 1024.0;
+                let humidity_f64 = cr.compensate_humidity_f64(adc_h, t_fine_f64);
+                assert!((humidity - humidity_f64).abs() < 0.01);
+            }
         }
     }
 }
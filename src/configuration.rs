@@ -1,3 +1,6 @@
+use crate::ChipModel;
+use embassy_time::Duration;
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct SamplingConfiguration {
     standby_duration: StandbyDuration,
@@ -28,7 +31,109 @@ impl From<&SamplingConfiguration> for (Config, ControlMeasurement, ControlHumidi
     }
 }
 
+impl From<(Config, ControlMeasurement, ControlHumidity)> for SamplingConfiguration {
+    fn from((config, ctrl_meas, ctrl_hum): (Config, ControlMeasurement, ControlHumidity)) -> Self {
+        Self {
+            standby_duration: config.standby_duration(),
+            filter: config.filter(),
+            spi3w: config.spi3w(),
+            temperature_oversampling: ctrl_meas.temperature_oversampling(),
+            pressure_oversampling: ctrl_meas.pressure_oversampling(),
+            humidity_oversampling: ctrl_hum.humidity_oversampling(),
+            sensor_mode: ctrl_meas.sensor_mode(),
+        }
+    }
+}
+
+/// Recommended settings from section 3.5 of the BME280 datasheet. Current,
+/// noise and data rate figures are the datasheet values.
+impl SamplingConfiguration {
+    /// Weather monitoring: forced mode, x1 oversampling on all channels,
+    /// filter off.
+    ///
+    /// At one `measure_once` per minute: 0.16 µA, 1/60 Hz output data rate,
+    /// 3.3 Pa (30 cm) and 0.07 %RH RMS noise.
+    pub fn weather_monitoring() -> Self {
+        Self::default()
+            .with_sensor_mode(SensorMode::Forced)
+            .with_temperature_oversampling(Oversampling::X1)
+            .with_pressure_oversampling(Oversampling::X1)
+            .with_humidity_oversampling(Oversampling::X1)
+            .with_filter(Filter::Off)
+    }
+
+    /// Humidity sensing: forced mode, pressure skipped, x1 oversampling on
+    /// temperature and humidity, filter off.
+    ///
+    /// At one `measure_once` per second: 2.9 µA, 1 Hz output data rate,
+    /// 0.07 %RH RMS noise.
+    pub fn humidity_sensing() -> Self {
+        Self::default()
+            .with_sensor_mode(SensorMode::Forced)
+            .with_temperature_oversampling(Oversampling::X1)
+            .with_pressure_oversampling(Oversampling::Skip)
+            .with_humidity_oversampling(Oversampling::X1)
+            .with_filter(Filter::Off)
+    }
+
+    /// Indoor navigation: normal mode with 0.5 ms standby, pressure x16,
+    /// temperature x2, humidity x1, filter coefficient 16.
+    ///
+    /// 633 µA, 25 Hz output data rate, 0.2 Pa (1.7 cm) RMS noise, 0.9 s
+    /// response time (75 %).
+    pub fn indoor_navigation() -> Self {
+        Self::default()
+            .with_sensor_mode(SensorMode::Normal)
+            .with_standby_duration(StandbyDuration::Millis0_5)
+            .with_temperature_oversampling(Oversampling::X2)
+            .with_pressure_oversampling(Oversampling::X16)
+            .with_humidity_oversampling(Oversampling::X1)
+            .with_filter(Filter::X16)
+    }
+
+    /// Gaming: normal mode with 0.5 ms standby, pressure x4, temperature x1,
+    /// humidity skipped, filter coefficient 16.
+    ///
+    /// 581 µA, 83 Hz output data rate, 0.3 Pa (2.5 cm) RMS noise, 0.3 s
+    /// response time (75 %).
+    pub fn gaming() -> Self {
+        Self::default()
+            .with_sensor_mode(SensorMode::Normal)
+            .with_standby_duration(StandbyDuration::Millis0_5)
+            .with_temperature_oversampling(Oversampling::X1)
+            .with_pressure_oversampling(Oversampling::X4)
+            .with_humidity_oversampling(Oversampling::Skip)
+            .with_filter(Filter::X16)
+    }
+}
+
 impl SamplingConfiguration {
+    pub(crate) fn spi3w(&self) -> bool {
+        self.spi3w
+    }
+
+    pub fn sensor_mode(&self) -> SensorMode {
+        self.sensor_mode
+    }
+
+    /// Whether `read_back`, as returned by
+    /// [`read_configuration`](crate::bme280::BME280Sensor::read_configuration),
+    /// shows this configuration applied.
+    ///
+    /// A chip in forced mode falls back to sleep mode after every
+    /// conversion, so the mode is ignored for a forced mode configuration.
+    pub fn matches_read_back(&self, read_back: &SamplingConfiguration) -> bool {
+        match self.sensor_mode {
+            SensorMode::Forced => {
+                let asleep = |configuration: &Self| {
+                    configuration.clone().with_sensor_mode(SensorMode::Sleep)
+                };
+                asleep(self) == asleep(read_back)
+            }
+            _ => self == read_back,
+        }
+    }
+
     pub(crate) fn to_low_level_configuration(
         &self,
     ) -> (Config, ControlMeasurement, ControlHumidity) {
@@ -45,8 +150,11 @@ impl SamplingConfiguration {
         self
     }
 
-    #[allow(unused)]
-    pub(crate) fn with_spi3w(mut self, spi3w: bool) -> Self {
+    /// Enables the 3-wire SPI interface (SDI used for both directions).
+    ///
+    /// Only meaningful when the sensor is driven through
+    /// [`SpiInterface`](crate::interface::SpiInterface).
+    pub fn with_spi3w(mut self, spi3w: bool) -> Self {
         self.spi3w = spi3w;
         self
     }
@@ -75,10 +183,71 @@ impl SamplingConfiguration {
         self.standby_duration = standby_duration;
         self
     }
+
+    /// Typical duration of one conversion (datasheet appendix B).
+    pub fn typical_measurement_time(&self) -> Duration {
+        self.measurement_time(1000, 2000, 500)
+    }
+
+    /// Maximum duration of one conversion (datasheet appendix B).
+    ///
+    /// Waiting this long after triggering a forced conversion guarantees
+    /// the data registers hold the new result.
+    pub fn max_measurement_time(&self) -> Duration {
+        self.measurement_time(1250, 2300, 575)
+    }
+
+    /// Time between two conversions in normal mode on a BME280, i.e. the
+    /// inverse of the output data rate: typical measurement time plus
+    /// standby time.
+    pub fn normal_mode_period(&self) -> Duration {
+        self.normal_mode_period_on(ChipModel::BME280)
+    }
+
+    /// Time between two conversions in normal mode on `chip_model`, see
+    /// [`StandbyDuration::duration_on`].
+    pub fn normal_mode_period_on(&self, chip_model: ChipModel) -> Duration {
+        self.typical_measurement_time() + self.standby_duration.duration_on(chip_model)
+    }
+
+    fn measurement_time(&self, base_us: u64, per_sample_us: u64, overhead_us: u64) -> Duration {
+        let temperature = per_sample_us * self.temperature_oversampling.factor();
+        let channel = |oversampling: Oversampling| match oversampling.factor() {
+            0 => 0,
+            factor => per_sample_us * factor + overhead_us,
+        };
+        Duration::from_micros(
+            base_us
+                + temperature
+                + channel(self.pressure_oversampling)
+                + channel(self.humidity_oversampling),
+        )
+    }
 }
 
+/// Value of the config register 0xF5.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub(crate) struct Config(u8);
+pub struct Config(u8);
+
+impl Config {
+    pub fn standby_duration(self) -> StandbyDuration {
+        decode(self.0 >> 5)
+    }
+
+    pub fn filter(self) -> Filter {
+        decode(self.0 >> 2)
+    }
+
+    pub fn spi3w(self) -> bool {
+        self.0 & 0b1 != 0
+    }
+}
+
+impl From<u8> for Config {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
 
 impl From<(StandbyDuration, Filter, bool)> for Config {
     fn from((standby_duration, filter, spi3w): (StandbyDuration, Filter, bool)) -> Self {
@@ -95,8 +264,21 @@ impl From<Config> for u8 {
     }
 }
 
+/// Value of the ctrl_hum register 0xF2.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub(crate) struct ControlHumidity(u8);
+pub struct ControlHumidity(u8);
+
+impl ControlHumidity {
+    pub fn humidity_oversampling(self) -> Oversampling {
+        decode(self.0)
+    }
+}
+
+impl From<u8> for ControlHumidity {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
 
 impl From<Oversampling> for ControlHumidity {
     fn from(humidity_oversampling: Oversampling) -> Self {
@@ -110,8 +292,37 @@ impl From<ControlHumidity> for u8 {
     }
 }
 
+/// Value of the ctrl_meas register 0xF4.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub(crate) struct ControlMeasurement(u8);
+pub struct ControlMeasurement(u8);
+
+impl ControlMeasurement {
+    pub fn temperature_oversampling(self) -> Oversampling {
+        decode(self.0 >> 5)
+    }
+
+    pub fn pressure_oversampling(self) -> Oversampling {
+        decode(self.0 >> 2)
+    }
+
+    pub fn sensor_mode(self) -> SensorMode {
+        decode(self.0 & 0b11)
+    }
+}
+
+impl From<u8> for ControlMeasurement {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
+
+/// Decodes a 3-bit (or 2-bit) register field, every code of which is valid.
+fn decode<T: TryFrom<u8>>(field: u8) -> T {
+    match T::try_from(field & 0b111) {
+        Ok(value) => value,
+        Err(_) => unreachable!(),
+    }
+}
 
 impl From<(Oversampling, Oversampling, SensorMode)> for ControlMeasurement {
     fn from(
@@ -148,6 +359,53 @@ pub enum StandbyDuration {
     Millis1000 = 0b101,
 }
 
+impl TryFrom<u8> for StandbyDuration {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b000 => Ok(StandbyDuration::Millis0_5),
+            0b001 => Ok(StandbyDuration::Millis62_5),
+            0b010 => Ok(StandbyDuration::Millis125),
+            0b011 => Ok(StandbyDuration::Millis250),
+            0b100 => Ok(StandbyDuration::Millis500),
+            0b101 => Ok(StandbyDuration::Millis1000),
+            0b110 => Ok(StandbyDuration::Millis10),
+            0b111 => Ok(StandbyDuration::Millis20),
+            value => Err(value),
+        }
+    }
+}
+
+impl StandbyDuration {
+    /// Inactive time between two conversions in normal mode on a BME280.
+    pub fn duration(&self) -> Duration {
+        self.duration_on(ChipModel::BME280)
+    }
+
+    /// Inactive time between two conversions in normal mode on
+    /// `chip_model`. The BMP280 uses the codes of `Millis10` and `Millis20`
+    /// for 2000 ms and 4000 ms.
+    pub fn duration_on(&self, chip_model: ChipModel) -> Duration {
+        match self {
+            StandbyDuration::Millis0_5 => Duration::from_micros(500),
+            StandbyDuration::Millis10 => match chip_model {
+                ChipModel::BME280 => Duration::from_millis(10),
+                ChipModel::BMP280 => Duration::from_millis(2000),
+            },
+            StandbyDuration::Millis20 => match chip_model {
+                ChipModel::BME280 => Duration::from_millis(20),
+                ChipModel::BMP280 => Duration::from_millis(4000),
+            },
+            StandbyDuration::Millis62_5 => Duration::from_micros(62_500),
+            StandbyDuration::Millis125 => Duration::from_millis(125),
+            StandbyDuration::Millis250 => Duration::from_millis(250),
+            StandbyDuration::Millis500 => Duration::from_millis(500),
+            StandbyDuration::Millis1000 => Duration::from_millis(1000),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum Oversampling {
     #[default]
@@ -159,6 +417,37 @@ pub enum Oversampling {
     X16 = 0b101,
 }
 
+/// Codes 0b101 to 0b111 all select 16 times oversampling.
+impl TryFrom<u8> for Oversampling {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b000 => Ok(Oversampling::Skip),
+            0b001 => Ok(Oversampling::X1),
+            0b010 => Ok(Oversampling::X2),
+            0b011 => Ok(Oversampling::X4),
+            0b100 => Ok(Oversampling::X8),
+            0b101..=0b111 => Ok(Oversampling::X16),
+            value => Err(value),
+        }
+    }
+}
+
+impl Oversampling {
+    /// Number of samples averaged per conversion, 0 when skipped.
+    pub fn factor(self) -> u64 {
+        match self {
+            Oversampling::Skip => 0,
+            Oversampling::X1 => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 #[repr(u8)]
 pub enum SensorMode {
@@ -168,6 +457,20 @@ pub enum SensorMode {
     Normal = 0b11,
 }
 
+/// Codes 0b01 and 0b10 both select forced mode.
+impl TryFrom<u8> for SensorMode {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(SensorMode::Sleep),
+            0b01 | 0b10 => Ok(SensorMode::Forced),
+            0b11 => Ok(SensorMode::Normal),
+            value => Err(value),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[repr(u8)]
 pub enum Filter {
@@ -178,3 +481,194 @@ pub enum Filter {
     X8 = 0b011,
     X16 = 0b100,
 }
+
+/// Codes 0b100 to 0b111 all select a filter coefficient of 16.
+impl TryFrom<u8> for Filter {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b000 => Ok(Filter::Off),
+            0b001 => Ok(Filter::X2),
+            0b010 => Ok(Filter::X4),
+            0b011 => Ok(Filter::X8),
+            0b100..=0b111 => Ok(Filter::X16),
+            value => Err(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oversampling(t: Oversampling, p: Oversampling, h: Oversampling) -> SamplingConfiguration {
+        SamplingConfiguration::default()
+            .with_temperature_oversampling(t)
+            .with_pressure_oversampling(p)
+            .with_humidity_oversampling(h)
+    }
+
+    #[test]
+    fn measurement_time_for_single_oversampling() {
+        let configuration = oversampling(Oversampling::X1, Oversampling::X1, Oversampling::X1);
+
+        assert_eq!(
+            configuration.typical_measurement_time(),
+            Duration::from_millis(8)
+        );
+        assert_eq!(
+            configuration.max_measurement_time(),
+            Duration::from_micros(9_300)
+        );
+    }
+
+    #[test]
+    fn measurement_time_for_indoor_navigation() {
+        let configuration = oversampling(Oversampling::X2, Oversampling::X16, Oversampling::X1);
+
+        assert_eq!(
+            configuration.typical_measurement_time(),
+            Duration::from_millis(40)
+        );
+        assert_eq!(
+            configuration.max_measurement_time(),
+            Duration::from_micros(46_100)
+        );
+    }
+
+    #[test]
+    fn measurement_time_omits_skipped_channels() {
+        let configuration = oversampling(Oversampling::X1, Oversampling::Skip, Oversampling::Skip);
+
+        assert_eq!(
+            configuration.typical_measurement_time(),
+            Duration::from_millis(3)
+        );
+        assert_eq!(
+            configuration.max_measurement_time(),
+            Duration::from_micros(3_550)
+        );
+    }
+
+    #[test]
+    fn registers_decode_to_configuration() {
+        let configuration = oversampling(Oversampling::X2, Oversampling::X16, Oversampling::X1)
+            .with_sensor_mode(SensorMode::Normal)
+            .with_standby_duration(StandbyDuration::Millis10)
+            .with_filter(Filter::X4)
+            .with_spi3w(true);
+
+        let registers = configuration.to_low_level_configuration();
+
+        assert_eq!(SamplingConfiguration::from(registers), configuration);
+    }
+
+    #[test]
+    fn aliased_codes_decode() {
+        assert_eq!(Oversampling::try_from(0b111), Ok(Oversampling::X16));
+        assert_eq!(Filter::try_from(0b101), Ok(Filter::X16));
+        assert_eq!(SensorMode::try_from(0b10), Ok(SensorMode::Forced));
+        assert_eq!(Oversampling::try_from(8), Err(8));
+        assert_eq!(SensorMode::try_from(4), Err(4));
+        assert_eq!(
+            StandbyDuration::try_from(0b110),
+            Ok(StandbyDuration::Millis10)
+        );
+        // osrs_t = x16 (0b110), osrs_p = skip, mode = forced (0b10)
+        let ctrl_meas = ControlMeasurement::from(0b1100_0010);
+        assert_eq!(ctrl_meas.temperature_oversampling(), Oversampling::X16);
+        assert_eq!(ctrl_meas.pressure_oversampling(), Oversampling::Skip);
+        assert_eq!(ctrl_meas.sensor_mode(), SensorMode::Forced);
+    }
+
+    #[test]
+    fn forced_mode_matches_sleeping_chip() {
+        let configuration = SamplingConfiguration::weather_monitoring();
+        let asleep = configuration.clone().with_sensor_mode(SensorMode::Sleep);
+        let normal = SamplingConfiguration::gaming();
+
+        assert!(configuration.matches_read_back(&asleep));
+        assert!(configuration.matches_read_back(&configuration));
+        assert!(!configuration.matches_read_back(&asleep.clone().with_filter(Filter::X2)));
+        assert!(normal.matches_read_back(&normal));
+        assert!(!normal.matches_read_back(&normal.clone().with_sensor_mode(SensorMode::Sleep)));
+    }
+
+    /// Encoded (ctrl_hum, ctrl_meas, config) register values.
+    fn registers(configuration: SamplingConfiguration) -> (u8, u8, u8) {
+        let (config, ctrl_meas, ctrl_hum) = configuration.to_low_level_configuration();
+        (ctrl_hum.into(), ctrl_meas.into(), config.into())
+    }
+
+    #[test]
+    fn weather_monitoring_preset() {
+        // osrs_t = x1, osrs_p = x1, mode = forced
+        assert_eq!(
+            registers(SamplingConfiguration::weather_monitoring()),
+            (0b001, 0b0010_0101, 0b0000_0000)
+        );
+    }
+
+    #[test]
+    fn humidity_sensing_preset() {
+        // osrs_t = x1, osrs_p = skip, mode = forced
+        assert_eq!(
+            registers(SamplingConfiguration::humidity_sensing()),
+            (0b001, 0b0010_0001, 0b0000_0000)
+        );
+    }
+
+    #[test]
+    fn indoor_navigation_preset() {
+        // osrs_t = x2, osrs_p = x16, mode = normal; t_sb = 0.5 ms, filter = 16
+        assert_eq!(
+            registers(SamplingConfiguration::indoor_navigation()),
+            (0b001, 0b0101_0111, 0b0001_0000)
+        );
+    }
+
+    #[test]
+    fn gaming_preset() {
+        // osrs_t = x1, osrs_p = x4, mode = normal; t_sb = 0.5 ms, filter = 16
+        assert_eq!(
+            registers(SamplingConfiguration::gaming()),
+            (0b000, 0b0010_1111, 0b0001_0000)
+        );
+        // About 83 Hz.
+        assert_eq!(
+            SamplingConfiguration::gaming().normal_mode_period(),
+            Duration::from_millis(12)
+        );
+    }
+
+    #[test]
+    fn bmp280_standby_codes_are_longer() {
+        let configuration = oversampling(Oversampling::X1, Oversampling::X1, Oversampling::Skip)
+            .with_standby_duration(StandbyDuration::Millis20);
+
+        assert_eq!(
+            StandbyDuration::Millis10.duration_on(ChipModel::BMP280),
+            Duration::from_millis(2000)
+        );
+        assert_eq!(
+            configuration.normal_mode_period_on(ChipModel::BMP280),
+            Duration::from_micros(4_005_500)
+        );
+        assert_eq!(
+            configuration.normal_mode_period(),
+            Duration::from_micros(25_500)
+        );
+    }
+
+    #[test]
+    fn normal_mode_period_includes_standby() {
+        let configuration = oversampling(Oversampling::X1, Oversampling::X1, Oversampling::X1)
+            .with_standby_duration(StandbyDuration::Millis62_5);
+
+        assert_eq!(
+            configuration.normal_mode_period(),
+            Duration::from_micros(70_500)
+        );
+    }
+}
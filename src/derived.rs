@@ -0,0 +1,391 @@
+//! Derived meteorological quantities.
+//!
+//! Inputs and results use the types of the [`units`](crate::units) module,
+//! like [`BME280Response`]. Saturation vapour pressure uses the Magnus formula
+//! with the Sonntag (1990) coefficients, valid from -45 °C to 60 °C over water
+//! and from -65 °C to 0 °C over ice.
+
+use crate::units::{
+    AbsoluteHumidity, Celsius, Meters, MixingRatio, Pascal, RelativeHumidity,
+    ZERO_CELSIUS_IN_KELVIN,
+};
+use crate::BME280Response;
+use libm::{expf, logf, powf, sqrtf};
+
+const MAGNUS_WATER_B: f32 = 17.62;
+const MAGNUS_WATER_C: f32 = 243.12;
+const MAGNUS_ICE_B: f32 = 22.46;
+const MAGNUS_ICE_C: f32 = 272.62;
+const MAGNUS_PRESSURE: f32 = 611.2;
+/// Lowest relative humidity the sensor resolves, 1/1024 %. Dew and frost
+/// point are taken at this humidity for a 0 % reading, where the logarithm
+/// of the Magnus formula has no finite value.
+const MIN_HUMIDITY: f32 = 1.0 / 1024.0;
+
+/// Specific gas constant of water vapour, J/(kg·K).
+const WATER_VAPOUR_GAS_CONSTANT: f32 = 461.5;
+/// Ratio of the molar masses of water vapour and dry air, in g/kg.
+const MOLAR_MASS_RATIO: f32 = 621.97;
+
+/// Mean sea-level pressure of the ICAO standard atmosphere.
+pub const STANDARD_SEA_LEVEL_PRESSURE: Pascal = Pascal(101_325.0);
+/// Temperature lapse rate of the standard atmosphere, K/m.
+const LAPSE_RATE: f32 = 0.0065;
+const BAROMETRIC_EXPONENT: f32 = 5.257;
+
+/// Saturation vapour pressure over water.
+pub fn saturation_vapour_pressure(Celsius(temperature): Celsius) -> Pascal {
+    Pascal(MAGNUS_PRESSURE * expf(MAGNUS_WATER_B * temperature / (MAGNUS_WATER_C + temperature)))
+}
+
+/// Partial pressure of water vapour.
+pub fn vapour_pressure(
+    temperature: Celsius,
+    RelativeHumidity(humidity): RelativeHumidity,
+) -> Pascal {
+    Pascal(humidity / 100.0 * saturation_vapour_pressure(temperature).0)
+}
+
+/// Dew point (Magnus formula).
+///
+/// Humidity below 1/1024 %, the sensor resolution, counts as 1/1024 %, so a
+/// 0 % reading gives a very low dew point instead of NaN.
+pub fn dew_point(
+    Celsius(temperature): Celsius,
+    RelativeHumidity(humidity): RelativeHumidity,
+) -> Celsius {
+    let gamma = logf(humidity.max(MIN_HUMIDITY) / 100.0)
+        + MAGNUS_WATER_B * temperature / (MAGNUS_WATER_C + temperature);
+    Celsius(MAGNUS_WATER_C * gamma / (MAGNUS_WATER_B - gamma))
+}
+
+/// Frost point, the temperature at which the air saturates over ice.
+///
+/// `humidity` is relative to water, as reported by the sensor. Like in
+/// [`dew_point`], a 0 % reading counts as 1/1024 %.
+pub fn frost_point(temperature: Celsius, RelativeHumidity(humidity): RelativeHumidity) -> Celsius {
+    let humidity = RelativeHumidity(humidity.max(MIN_HUMIDITY));
+    let gamma = logf(vapour_pressure(temperature, humidity).0 / MAGNUS_PRESSURE);
+    Celsius(MAGNUS_ICE_C * gamma / (MAGNUS_ICE_B - gamma))
+}
+
+/// Absolute humidity, the mass of water vapour per volume of air.
+pub fn absolute_humidity(temperature: Celsius, humidity: RelativeHumidity) -> AbsoluteHumidity {
+    AbsoluteHumidity(
+        vapour_pressure(temperature, humidity).0 * 1000.0
+            / (WATER_VAPOUR_GAS_CONSTANT * (temperature.0 + ZERO_CELSIUS_IN_KELVIN)),
+    )
+}
+
+/// Mixing ratio, the mass of water vapour per mass of dry air.
+pub fn mixing_ratio(
+    temperature: Celsius,
+    humidity: RelativeHumidity,
+    Pascal(pressure): Pascal,
+) -> MixingRatio {
+    let Pascal(vapour_pressure) = vapour_pressure(temperature, humidity);
+    MixingRatio(MOLAR_MASS_RATIO * vapour_pressure / (pressure - vapour_pressure))
+}
+
+/// Vapour pressure deficit, the difference between saturation and actual
+/// vapour pressure.
+pub fn vapour_pressure_deficit(
+    temperature: Celsius,
+    RelativeHumidity(humidity): RelativeHumidity,
+) -> Pascal {
+    Pascal(saturation_vapour_pressure(temperature).0 * (1.0 - humidity / 100.0))
+}
+
+/// Heat index as computed by the US National Weather Service (Steadman
+/// simple formula, Rothfusz regression above 80 °F).
+pub fn heat_index(
+    Celsius(temperature): Celsius,
+    RelativeHumidity(humidity): RelativeHumidity,
+) -> Celsius {
+    let t = temperature * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let fahrenheit = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let regression = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+            - 0.224_755_42 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            regression - (13.0 - rh) / 4.0 * sqrtf((17.0 - (t - 95.0).abs()) / 17.0)
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            regression + (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0)
+        } else {
+            regression
+        }
+    };
+
+    Celsius((fahrenheit - 32.0) * 5.0 / 9.0)
+}
+
+/// Humidex as defined by Environment Canada.
+pub fn humidex(temperature: Celsius, humidity: RelativeHumidity) -> Celsius {
+    let dew_point = dew_point(temperature, humidity).0 + ZERO_CELSIUS_IN_KELVIN;
+    let vapour_pressure = 6.11 * expf(5417.753 * (1.0 / 273.16 - 1.0 / dew_point));
+    Celsius(temperature.0 + 0.5555 * (vapour_pressure - 10.0))
+}
+
+/// Altitude from the barometric formula of the standard atmosphere.
+///
+/// `sea_level_pressure` is the reference pressure (QNH), use
+/// [`STANDARD_SEA_LEVEL_PRESSURE`] for pressure altitude.
+pub fn altitude(Pascal(pressure): Pascal, Pascal(sea_level_pressure): Pascal) -> Meters {
+    let standard_temperature = 15.0 + ZERO_CELSIUS_IN_KELVIN;
+    Meters(
+        standard_temperature / LAPSE_RATE
+            * (1.0 - powf(pressure / sea_level_pressure, 1.0 / BAROMETRIC_EXPONENT)),
+    )
+}
+
+/// Pressure reduced to sea level from the station `pressure` measured at a
+/// known `altitude`.
+///
+/// The air column below the station is assumed to follow the standard lapse
+/// rate from the station `temperature`.
+pub fn sea_level_pressure(
+    Pascal(pressure): Pascal,
+    Meters(altitude): Meters,
+    Celsius(temperature): Celsius,
+) -> Pascal {
+    let column = LAPSE_RATE * altitude;
+    Pascal(
+        pressure
+            * powf(
+                1.0 - column / (temperature + column + ZERO_CELSIUS_IN_KELVIN),
+                -BAROMETRIC_EXPONENT,
+            ),
+    )
+}
+
+impl BME280Response {
+    fn temperature_and_humidity(&self) -> Option<(Celsius, RelativeHumidity)> {
+        Some((self.temperature?, self.humidity?))
+    }
+
+    /// See [`dew_point`].
+    pub fn dew_point(&self) -> Option<Celsius> {
+        self.temperature_and_humidity()
+            .map(|(temperature, humidity)| dew_point(temperature, humidity))
+    }
+
+    /// See [`frost_point`].
+    pub fn frost_point(&self) -> Option<Celsius> {
+        self.temperature_and_humidity()
+            .map(|(temperature, humidity)| frost_point(temperature, humidity))
+    }
+
+    /// See [`absolute_humidity`].
+    pub fn absolute_humidity(&self) -> Option<AbsoluteHumidity> {
+        self.temperature_and_humidity()
+            .map(|(temperature, humidity)| absolute_humidity(temperature, humidity))
+    }
+
+    /// See [`mixing_ratio`].
+    pub fn mixing_ratio(&self) -> Option<MixingRatio> {
+        let (temperature, humidity) = self.temperature_and_humidity()?;
+        self.pressure
+            .map(|pressure| mixing_ratio(temperature, humidity, pressure))
+    }
+
+    /// See [`vapour_pressure_deficit`].
+    pub fn vapour_pressure_deficit(&self) -> Option<Pascal> {
+        self.temperature_and_humidity()
+            .map(|(temperature, humidity)| vapour_pressure_deficit(temperature, humidity))
+    }
+
+    /// See [`heat_index`].
+    pub fn heat_index(&self) -> Option<Celsius> {
+        self.temperature_and_humidity()
+            .map(|(temperature, humidity)| heat_index(temperature, humidity))
+    }
+
+    /// See [`humidex`].
+    pub fn humidex(&self) -> Option<Celsius> {
+        self.temperature_and_humidity()
+            .map(|(temperature, humidity)| humidex(temperature, humidity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn dew_point_matches_table() {
+        assert_close(
+            dew_point(Celsius(25.0), RelativeHumidity(60.0)).0,
+            16.7,
+            0.1,
+        );
+        assert_close(dew_point(Celsius(20.0), RelativeHumidity(50.0)).0, 9.3, 0.1);
+        assert_close(
+            dew_point(Celsius(10.0), RelativeHumidity(100.0)).0,
+            10.0,
+            0.01,
+        );
+    }
+
+    #[test]
+    fn frost_point_is_above_dew_point_below_freezing() {
+        let frost_point = frost_point(Celsius(-10.0), RelativeHumidity(80.0));
+
+        assert_close(frost_point.0, -11.4, 0.1);
+        assert!(frost_point > dew_point(Celsius(-10.0), RelativeHumidity(80.0)));
+    }
+
+    #[test]
+    fn dry_air_gives_finite_values() {
+        let dry = RelativeHumidity(0.0);
+
+        assert_close(dew_point(Celsius(25.0), dry).0, -87.4, 0.1);
+        assert_close(frost_point(Celsius(25.0), dry).0, -83.4, 0.1);
+        assert_close(humidex(Celsius(25.0), dry).0, 19.4, 0.1);
+    }
+
+    #[test]
+    fn absolute_humidity_matches_table() {
+        assert_close(
+            absolute_humidity(Celsius(20.0), RelativeHumidity(50.0)).0,
+            8.6,
+            0.1,
+        );
+        assert_close(
+            absolute_humidity(Celsius(30.0), RelativeHumidity(100.0)).0,
+            30.4,
+            0.2,
+        );
+    }
+
+    #[test]
+    fn mixing_ratio_at_standard_pressure() {
+        assert_close(
+            mixing_ratio(
+                Celsius(20.0),
+                RelativeHumidity(50.0),
+                STANDARD_SEA_LEVEL_PRESSURE,
+            )
+            .0,
+            7.2,
+            0.1,
+        );
+    }
+
+    #[test]
+    fn vapour_pressure_deficit_matches_table() {
+        assert_close(
+            vapour_pressure_deficit(Celsius(25.0), RelativeHumidity(60.0)).0,
+            1270.0,
+            10.0,
+        );
+        assert_close(
+            vapour_pressure_deficit(Celsius(25.0), RelativeHumidity(100.0)).0,
+            0.0,
+            0.001,
+        );
+    }
+
+    #[test]
+    fn heat_index_matches_nws_table() {
+        // 90 °F at 70 % is 106 °F.
+        assert_close(
+            heat_index(Celsius(32.22), RelativeHumidity(70.0)).0,
+            41.1,
+            0.2,
+        );
+        // Below 80 °F the simple formula is close to the air temperature.
+        assert_close(
+            heat_index(Celsius(26.67), RelativeHumidity(40.0)).0,
+            26.4,
+            0.2,
+        );
+    }
+
+    #[test]
+    fn humidex_matches_environment_canada_table() {
+        // 30 °C with a 15 °C dew point is a humidex of 34.
+        let humidity = 100.0 * saturation_vapour_pressure(Celsius(15.0)).0
+            / saturation_vapour_pressure(Celsius(30.0)).0;
+
+        assert_close(
+            humidex(Celsius(30.0), RelativeHumidity(humidity)).0,
+            34.0,
+            0.2,
+        );
+    }
+
+    #[test]
+    fn altitude_matches_standard_atmosphere() {
+        let station = Pascal(89_874.6);
+
+        assert_close(
+            altitude(station, STANDARD_SEA_LEVEL_PRESSURE).0,
+            1000.0,
+            1.0,
+        );
+        assert_close(
+            altitude(STANDARD_SEA_LEVEL_PRESSURE, STANDARD_SEA_LEVEL_PRESSURE).0,
+            0.0,
+            0.01,
+        );
+        // A higher QNH puts the same station pressure higher up.
+        assert!(altitude(station, Pascal(102_000.0)) > Meters(1000.0));
+    }
+
+    #[test]
+    fn sea_level_pressure_inverts_altitude() {
+        // Standard atmosphere at 1000 m: 89874.6 Pa and 8.5 °C.
+        assert_close(
+            sea_level_pressure(Pascal(89_874.6), Meters(1000.0), Celsius(8.5)).0,
+            101_325.0,
+            10.0,
+        );
+        assert_close(
+            sea_level_pressure(Pascal(100_000.0), Meters(0.0), Celsius(20.0)).0,
+            100_000.0,
+            0.01,
+        );
+    }
+
+    #[test]
+    fn response_without_humidity_has_no_derived_values() {
+        let response = BME280Response {
+            humidity: None,
+            temperature: Some(Celsius(20.0)),
+            pressure: Some(Pascal(101_325.0)),
+            altitude: None,
+        };
+
+        assert!(response.dew_point().is_none());
+        assert!(response.mixing_ratio().is_none());
+    }
+
+    #[test]
+    fn response_derived_values() {
+        let response = BME280Response {
+            humidity: Some(RelativeHumidity(50.0)),
+            temperature: Some(Celsius(20.0)),
+            pressure: Some(Pascal(101_325.0)),
+            altitude: None,
+        };
+
+        assert_close(response.dew_point().unwrap().0, 9.3, 0.1);
+        assert_close(response.mixing_ratio().unwrap().0, 7.2, 0.1);
+    }
+}
@@ -0,0 +1,172 @@
+//! Sensor that owns its bus.
+//!
+//! [`BME280Sensor`] takes the bus on every call, which suits a bus shared by
+//! several drivers within one task. [`BME280Device`] keeps the bus, or a
+//! handle on it such as `&mut I2c` or a
+//! [`shared_bus::I2cDevice`](crate::shared_bus::I2cDevice), next to the
+//! sensor state instead.
+
+use crate::bme280::BME280Sensor;
+use crate::configuration::{Filter, SamplingConfiguration, SensorMode, StandbyDuration};
+use crate::interface::{Address, I2cInterface, Interface, SpiInterface};
+use crate::{BME280Error, BME280IntegerResponse, BME280RawResponse, BME280Response};
+
+pub struct BME280Device<BUS, IF = I2cInterface> {
+    sensor: BME280Sensor<IF>,
+    bus: BUS,
+}
+
+impl<BUS> BME280Device<BUS, I2cInterface> {
+    pub fn new(bus: BUS, address: impl Into<Address>) -> Self {
+        Self::from_sensor(BME280Sensor::new(address), bus)
+    }
+}
+
+impl<BUS> BME280Device<BUS, SpiInterface> {
+    pub fn new_spi(bus: BUS) -> Self {
+        Self::from_sensor(BME280Sensor::new_spi(), bus)
+    }
+}
+
+impl<BUS, IF> BME280Device<BUS, IF> {
+    /// Pairs a sensor, e.g. one built with `with_compensation` or
+    /// `with_calibration_registers`, with its bus.
+    pub fn from_sensor(sensor: BME280Sensor<IF>, bus: BUS) -> Self {
+        Self { sensor, bus }
+    }
+
+    pub fn sensor(&self) -> &BME280Sensor<IF> {
+        &self.sensor
+    }
+
+    pub fn sensor_mut(&mut self) -> &mut BME280Sensor<IF> {
+        &mut self.sensor
+    }
+
+    /// Gives back the bus.
+    pub fn release(self) -> BUS {
+        self.bus
+    }
+
+    /// Gives back the sensor, keeping its calibration, and the bus.
+    pub fn into_parts(self) -> (BME280Sensor<IF>, BUS) {
+        (self.sensor, self.bus)
+    }
+}
+
+impl<BUS, IF> BME280Device<BUS, IF>
+where
+    IF: Interface<BUS>,
+{
+    /// See [`BME280Sensor::setup`].
+    pub async fn setup(
+        &mut self,
+        sampling_configuration: SamplingConfiguration,
+    ) -> Result<(), BME280Error> {
+        self.sensor
+            .setup(&mut self.bus, sampling_configuration)
+            .await
+    }
+
+    /// See [`BME280Sensor::read_configuration`].
+    pub async fn read_configuration(&mut self) -> Result<SamplingConfiguration, BME280Error> {
+        self.sensor.read_configuration(&mut self.bus).await
+    }
+
+    /// See [`BME280Sensor::reconfigure`].
+    pub async fn reconfigure(
+        &mut self,
+        sampling_configuration: SamplingConfiguration,
+    ) -> Result<(), BME280Error> {
+        self.sensor
+            .reconfigure(&mut self.bus, sampling_configuration)
+            .await
+    }
+
+    /// See [`BME280Sensor::set_sensor_mode`].
+    pub async fn set_sensor_mode(&mut self, sensor_mode: SensorMode) -> Result<(), BME280Error> {
+        self.sensor
+            .set_sensor_mode(&mut self.bus, sensor_mode)
+            .await
+    }
+
+    /// See [`BME280Sensor::set_filter`].
+    pub async fn set_filter(&mut self, filter: Filter) -> Result<(), BME280Error> {
+        self.sensor.set_filter(&mut self.bus, filter).await
+    }
+
+    /// See [`BME280Sensor::set_standby_duration`].
+    pub async fn set_standby_duration(
+        &mut self,
+        standby_duration: StandbyDuration,
+    ) -> Result<(), BME280Error> {
+        self.sensor
+            .set_standby_duration(&mut self.bus, standby_duration)
+            .await
+    }
+
+    /// See [`BME280Sensor::measure_once`].
+    pub async fn measure_once(&mut self) -> Result<BME280Response, BME280Error> {
+        self.sensor.measure_once(&mut self.bus).await
+    }
+
+    /// See [`BME280Sensor::measure_once_integer`].
+    pub async fn measure_once_integer(&mut self) -> Result<BME280IntegerResponse, BME280Error> {
+        self.sensor.measure_once_integer(&mut self.bus).await
+    }
+
+    /// See [`BME280Sensor::read`].
+    pub async fn read(&mut self) -> Result<BME280Response, BME280Error> {
+        self.sensor.read(&mut self.bus).await
+    }
+
+    /// See [`BME280Sensor::read_integer`].
+    pub async fn read_integer(&mut self) -> Result<BME280IntegerResponse, BME280Error> {
+        self.sensor.read_integer(&mut self.bus).await
+    }
+
+    /// See [`BME280Sensor::read_raw`].
+    pub async fn read_raw(&mut self) -> Result<BME280RawResponse, BME280Error> {
+        self.sensor.read_raw(&mut self.bus).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::Compensation;
+    use crate::simulator::{reference_configuration, Simulator, SIMULATOR_ADDRESS};
+    use crate::units::Celsius;
+    use crate::ChipModel;
+    use embassy_futures::block_on;
+
+    #[test]
+    fn borrowed_bus_is_released() {
+        let mut bus = Simulator::new();
+        let mut device = BME280Device::new(&mut bus, SIMULATOR_ADDRESS);
+        block_on(device.setup(reference_configuration().with_sensor_mode(SensorMode::Forced)))
+            .unwrap();
+
+        let response = block_on(device.measure_once()).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(device.sensor().chip_model(), Some(ChipModel::BME280));
+        device.release();
+        assert_eq!(bus.conversions, 2);
+    }
+
+    #[test]
+    fn owned_bus_is_released() {
+        let sensor =
+            BME280Sensor::new(SIMULATOR_ADDRESS).with_compensation(Compensation::FloatingPoint);
+        let mut device = BME280Device::from_sensor(sensor, Simulator::new());
+        block_on(device.setup(reference_configuration().with_sensor_mode(SensorMode::Forced)))
+            .unwrap();
+
+        let response = block_on(device.read()).unwrap();
+
+        assert!((response.temperature.unwrap().0 - 25.0825).abs() < 0.0001);
+        let bus = device.release();
+        assert_eq!(bus.resets, 1);
+    }
+}
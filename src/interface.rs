@@ -0,0 +1,340 @@
+//! Bus interfaces the driver uses to reach the sensor registers.
+//!
+//! The BME280 exposes the same register map over I2C and SPI; only the
+//! framing differs. [`BME280Sensor`](crate::bme280::BME280Sensor) is
+//! parametrized by one of the interfaces below, and the bus itself is still
+//! passed to every call.
+
+use crate::{BME280Error, ChipModel, BME280_REGISTER_CHIPID};
+use embedded_hal_async::i2c::{Error, ErrorKind, I2c};
+use embedded_hal_async::spi::{Error as _, Operation, SpiDevice};
+
+/// Register level access to the sensor over a bus of type `BUS`.
+#[allow(async_fn_in_trait)]
+pub trait Interface<BUS> {
+    /// Burst read starting at `register` into `read`.
+    async fn read_registers(
+        &self,
+        bus: &mut BUS,
+        register: u8,
+        read: &mut [u8],
+    ) -> Result<(), BME280Error>;
+
+    /// Writes a single `data` byte into `register`.
+    async fn write_register(
+        &self,
+        bus: &mut BUS,
+        register: u8,
+        data: u8,
+    ) -> Result<(), BME280Error>;
+}
+
+/// I2C address of the sensor, selected by the level of the SDO pin.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Address {
+    /// SDO connected to GND, 0x76.
+    Primary,
+    /// SDO connected to VDDIO, 0x77.
+    Secondary,
+    /// Any other address, e.g. behind an address translator.
+    Custom(u8),
+}
+
+impl Address {
+    pub fn value(self) -> u8 {
+        match self {
+            Address::Primary => 0x76,
+            Address::Secondary => 0x77,
+            Address::Custom(address) => address,
+        }
+    }
+}
+
+impl From<u8> for Address {
+    fn from(address: u8) -> Self {
+        match address {
+            0x76 => Address::Primary,
+            0x77 => Address::Secondary,
+            address => Address::Custom(address),
+        }
+    }
+}
+
+impl From<Address> for u8 {
+    fn from(address: Address) -> Self {
+        address.value()
+    }
+}
+
+/// Sensors found by [`probe`] at the two standard addresses.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Probe {
+    pub primary: Option<ChipModel>,
+    pub secondary: Option<ChipModel>,
+}
+
+impl Probe {
+    /// Every sensor found, primary address first.
+    pub fn iter(&self) -> impl Iterator<Item = (Address, ChipModel)> {
+        [
+            self.primary
+                .map(|chip_model| (Address::Primary, chip_model)),
+            self.secondary
+                .map(|chip_model| (Address::Secondary, chip_model)),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Address of the first sensor found.
+    pub fn first(&self) -> Option<Address> {
+        self.iter().next().map(|(address, _)| address)
+    }
+}
+
+/// Looks for a BME280 or BMP280 at 0x76 and 0x77 by reading the chip id.
+///
+/// An address that does not acknowledge or holds another chip id is
+/// reported as empty; other bus errors fail the probe.
+pub async fn probe<I2C: I2c>(bus: &mut I2C) -> Result<Probe, BME280Error> {
+    Ok(Probe {
+        primary: probe_address(bus, Address::Primary).await?,
+        secondary: probe_address(bus, Address::Secondary).await?,
+    })
+}
+
+async fn probe_address<I2C: I2c>(
+    bus: &mut I2C,
+    address: Address,
+) -> Result<Option<ChipModel>, BME280Error> {
+    let mut chip_id = [0];
+    match bus
+        .write_read(address.value(), &[BME280_REGISTER_CHIPID], &mut chip_id)
+        .await
+    {
+        Ok(_) => Ok(ChipModel::from_chip_id(chip_id[0])),
+        Err(error) => match error.kind() {
+            ErrorKind::NoAcknowledge(_) => Ok(None),
+            kind => Err(BME280Error::I2CError(kind)),
+        },
+    }
+}
+
+/// I2C interface, addressing the sensor at a fixed 7-bit address.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct I2cInterface {
+    address: u8,
+}
+
+impl I2cInterface {
+    pub fn new(address: impl Into<Address>) -> Self {
+        Self {
+            address: address.into().value(),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address.into()
+    }
+}
+
+impl<I2C: I2c> Interface<I2C> for I2cInterface {
+    async fn read_registers(
+        &self,
+        bus: &mut I2C,
+        register: u8,
+        read: &mut [u8],
+    ) -> Result<(), BME280Error> {
+        match bus.write_read(self.address, &[register], read).await {
+            Ok(_) => Ok(()),
+            Err(error) => Err(BME280Error::I2CError(error.kind())),
+        }
+    }
+
+    async fn write_register(
+        &self,
+        bus: &mut I2C,
+        register: u8,
+        data: u8,
+    ) -> Result<(), BME280Error> {
+        match bus.write(self.address, &[register, data]).await {
+            Ok(_) => Ok(()),
+            Err(error) => Err(BME280Error::I2CError(error.kind())),
+        }
+    }
+}
+
+/// SPI interface (mode 0 or 3, 4-wire or 3-wire).
+///
+/// On SPI the register address is sent with bit 7 set for reads and
+/// cleared for writes. Chip select is handled by the `SpiDevice`.
+/// For 3-wire wiring enable
+/// [`with_spi3w`](crate::configuration::SamplingConfiguration::with_spi3w)
+/// in the configuration passed to `setup`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SpiInterface;
+
+const SPI_READ: u8 = 0x80;
+const SPI_WRITE_MASK: u8 = 0x7F;
+
+impl<SPI: SpiDevice> Interface<SPI> for SpiInterface {
+    async fn read_registers(
+        &self,
+        bus: &mut SPI,
+        register: u8,
+        read: &mut [u8],
+    ) -> Result<(), BME280Error> {
+        let address = [register | SPI_READ];
+        let mut operations = [Operation::Write(&address), Operation::Read(read)];
+        match bus.transaction(&mut operations).await {
+            Ok(_) => Ok(()),
+            Err(error) => Err(BME280Error::SpiError(error.kind())),
+        }
+    }
+
+    async fn write_register(
+        &self,
+        bus: &mut SPI,
+        register: u8,
+        data: u8,
+    ) -> Result<(), BME280Error> {
+        match bus.write(&[register & SPI_WRITE_MASK, data]).await {
+            Ok(_) => Ok(()),
+            Err(error) => Err(BME280Error::SpiError(error.kind())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bme280::BME280Sensor;
+    use crate::configuration::SensorMode;
+    use crate::simulator::{reference_configuration, SimulatedBoard, Simulator};
+    use crate::units::Celsius;
+    use embassy_futures::block_on;
+
+    fn board(sensors: Vec<Simulator>) -> SimulatedBoard {
+        SimulatedBoard {
+            sensors,
+            other_address: 0x3C,
+            other_writes: Vec::new(),
+        }
+    }
+
+    fn simulator(address: Address, chip_id: u8) -> Simulator {
+        let mut simulator = Simulator::new().with_chip_id(chip_id);
+        simulator.address = address.value();
+        simulator
+    }
+
+    #[test]
+    fn address_conversions() {
+        assert_eq!(Address::from(0x76), Address::Primary);
+        assert_eq!(Address::from(0x77), Address::Secondary);
+        assert_eq!(Address::from(0x10), Address::Custom(0x10));
+        assert_eq!(u8::from(Address::Secondary), 0x77);
+        assert_eq!(I2cInterface::new(0x77).address(), Address::Secondary);
+    }
+
+    #[test]
+    fn probe_finds_sensor_on_secondary_address() {
+        let mut bus = board(vec![simulator(Address::Secondary, 0x60)]);
+
+        let probe = block_on(probe(&mut bus)).unwrap();
+
+        assert_eq!(probe.primary, None);
+        assert_eq!(probe.secondary, Some(ChipModel::BME280));
+        assert_eq!(probe.first(), Some(Address::Secondary));
+    }
+
+    #[test]
+    fn probe_finds_both_sensors() {
+        let mut bus = board(vec![
+            simulator(Address::Primary, 0x58),
+            simulator(Address::Secondary, 0x60),
+        ]);
+
+        let probe = block_on(probe(&mut bus)).unwrap();
+
+        let found: Vec<_> = probe.iter().collect();
+        assert_eq!(
+            found,
+            [
+                (Address::Primary, ChipModel::BMP280),
+                (Address::Secondary, ChipModel::BME280)
+            ]
+        );
+    }
+
+    #[test]
+    fn probe_ignores_other_chips() {
+        let mut bus = board(vec![simulator(Address::Primary, 0x42)]);
+
+        let probe = block_on(probe(&mut bus)).unwrap();
+
+        assert_eq!(probe, Probe::default());
+        assert_eq!(probe.first(), None);
+    }
+
+    #[test]
+    fn probe_reports_bus_errors() {
+        let mut bus = Simulator::new();
+        bus.fault = Some(ErrorKind::ArbitrationLoss);
+
+        let result = block_on(probe(&mut bus));
+
+        assert_eq!(
+            result,
+            Err(BME280Error::I2CError(ErrorKind::ArbitrationLoss))
+        );
+    }
+
+    #[test]
+    fn spi_setup_and_read() {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new_spi();
+
+        let configuration = reference_configuration().with_sensor_mode(SensorMode::Normal);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        let response = block_on(sensor.read(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(bus.resets, 1);
+        // Chip id read with bit 7 set, then the reset written to 0xE0 as 0x60.
+        assert_eq!(bus.spi_control_bytes[..2], [0xD0, 0x60]);
+        // ctrl_hum, config and ctrl_meas are written as 0x72, 0x75 and 0x74.
+        assert!(bus.spi_control_bytes.contains(&0x72));
+        assert!(bus.spi_control_bytes.contains(&0x75));
+        assert_eq!(bus.register(0xF4), 0b0010_0111);
+    }
+
+    #[test]
+    fn spi_3_wire_is_enabled_before_chip_id_and_after_reset() {
+        let mut bus = Simulator::new();
+        bus.three_wire = true;
+        let mut sensor = BME280Sensor::new_spi();
+
+        let configuration = reference_configuration()
+            .with_sensor_mode(SensorMode::Forced)
+            .with_spi3w(true);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        let response = block_on(sensor.measure_once(&mut bus)).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(bus.spi_control_bytes[..3], [0x75, 0xD0, 0x60]);
+        assert_eq!(bus.writes[..3], [(0xF5, 0b1), (0xE0, 0xB6), (0xF5, 0b1)]);
+        assert_eq!(bus.register(0xF5) & 0b1, 0b1);
+    }
+
+    #[test]
+    fn spi_3_wire_chip_does_not_answer_without_spi3w() {
+        let mut bus = Simulator::new();
+        bus.three_wire = true;
+        let mut sensor = BME280Sensor::new_spi();
+
+        let result = block_on(sensor.setup(&mut bus, reference_configuration()));
+
+        assert_eq!(result, Err(BME280Error::InvalidChipId(0xFF)));
+    }
+}
@@ -1,14 +1,31 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
 
+pub mod bme280;
+#[cfg(feature = "rp2040")]
 pub mod bme280_rp;
 pub mod calibration;
 pub mod configuration;
+pub mod derived;
+pub mod device;
+pub mod interface;
+pub mod runner;
+pub mod shared_bus;
+#[cfg(test)]
+mod simulator;
+pub mod units;
+
+use crate::units::{Celsius, Meters, Pascal, RelativeHumidity};
+use core::fmt;
+use embedded_hal_async::{i2c, spi};
 
 const BME280_REGISTER_CHIPID: u8 = 0xD0;
 const BME280_REGISTER_SOFTRESET: u8 = 0xE0;
 const BME280_REGISTER_STATUS: u8 = 0xF3;
 
+const BME280_SOFTRESET_COMMAND: u8 = 0xB6;
+const BME280_STATUS_IM_UPDATE: u8 = 0b0000_0001;
+const BME280_STATUS_MEASURING: u8 = 0b0000_1000;
+
 const BME280_REGISTER_DIG_FIRST_START: u8 = 0x88;
 const BME280_REGISTER_DIG_FIRST_END: u8 = 0xA1 + size_of::<u8>() as u8;
 const BME280_REGISTER_DIG_FIRST_LENGTH: usize =
@@ -25,19 +42,196 @@ const BME280_REGISTER_CONFIG: u8 = 0xF5;
 
 const BME280_REGISTER_DATA_START: u8 = 0xF7;
 const BME280_REGISTER_DATA_LENGTH: usize = 8;
+const BMP280_REGISTER_DATA_LENGTH: usize = 6;
+
+/// Raw value of a 20-bit channel whose oversampling is `Skip`.
+const BME280_ADC_SKIPPED: u32 = 0x80000;
+/// Raw value of the humidity channel when its oversampling is `Skip`.
+const BME280_ADC_H_SKIPPED: u16 = 0x8000;
+
+/// Sensor variant detected from the chip id register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChipModel {
+    /// Temperature, pressure and humidity sensor (chip id 0x60).
+    BME280,
+    /// Temperature and pressure only sensor (chip id 0x56, 0x57 or 0x58).
+    ///
+    /// Standby codes 0b110 and 0b111 mean 2000 ms and 4000 ms on this chip,
+    /// so `StandbyDuration::Millis10` and `Millis20` select those instead.
+    BMP280,
+}
+
+impl ChipModel {
+    pub fn from_chip_id(chip_id: u8) -> Option<Self> {
+        match chip_id {
+            0x60 => Some(ChipModel::BME280),
+            0x56..=0x58 => Some(ChipModel::BMP280),
+            _ => None,
+        }
+    }
+
+    pub fn has_humidity(&self) -> bool {
+        *self == ChipModel::BME280
+    }
+}
 
+/// Compensated measurement.
+///
+/// A channel is `None` when its oversampling is `Oversampling::Skip`.
+/// Skipping temperature leaves all channels empty since pressure and
+/// humidity compensation depend on it.
 #[derive(Clone)]
 pub struct BME280Response {
-    pub humidity: f32,
-    pub temperature: f32,
-    pub pressure: f32,
+    /// Relative humidity, also `None` on a BMP280.
+    pub humidity: Option<RelativeHumidity>,
+    pub temperature: Option<Celsius>,
+    pub pressure: Option<Pascal>,
+    /// Barometric altitude, only set by
+    /// [`BME280Sensor::read`](bme280::BME280Sensor::read) when a sea-level
+    /// reference pressure is configured.
+    pub altitude: Option<Meters>,
+}
+
+/// Compensated measurement in the fixed point formats of the Bosch integer
+/// formulas, for firmware that never touches floating point.
+///
+/// Channels are `None` under the same conditions as in [`BME280Response`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BME280IntegerResponse {
+    /// Relative humidity in Q22.10 format, 47445 is 46.333 %.
+    pub humidity: Option<u32>,
+    /// Temperature in 0.01 °C, 5123 is 51.23 °C.
+    pub temperature: Option<i32>,
+    /// Pressure in Q24.8 format, 24674867 is 96386.2 Pa.
+    pub pressure: Option<u32>,
+}
+
+impl BME280IntegerResponse {
+    pub fn humidity_percent(&self) -> Option<RelativeHumidity> {
+        self.humidity
+            .map(|humidity| RelativeHumidity(humidity as f32 / 1024.0))
+    }
+
+    pub fn temperature_celsius(&self) -> Option<Celsius> {
+        self.temperature
+            .map(|temperature| Celsius(temperature as f32 / 100.0))
+    }
+
+    pub fn pressure_pascal(&self) -> Option<Pascal> {
+        self.pressure
+            .map(|pressure| Pascal(pressure as f32 / 256.0))
+    }
+}
+
+impl From<BME280IntegerResponse> for BME280Response {
+    fn from(response: BME280IntegerResponse) -> Self {
+        Self {
+            humidity: response.humidity_percent(),
+            temperature: response.temperature_celsius(),
+            pressure: response.pressure_pascal(),
+            altitude: None,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Uncompensated ADC readings as stored in the data registers 0xF7..=0xFE.
+///
+/// Skipped channels hold the reset values 0x80000 (pressure, temperature)
+/// and 0x8000 (humidity). Use
+/// [`CalibrationRegisters::compensate`](calibration::CalibrationRegisters::compensate)
+/// to turn them into a [`BME280Response`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BME280RawResponse {
+    /// 20-bit pressure reading.
+    pub pressure: u32,
+    /// 20-bit temperature reading.
+    pub temperature: u32,
+    /// 16-bit humidity reading.
+    pub humidity: u16,
+}
+
+impl From<[u8; BME280_REGISTER_DATA_LENGTH]> for BME280RawResponse {
+    fn from(data: [u8; BME280_REGISTER_DATA_LENGTH]) -> Self {
+        let data_msb = (data[0] as u32) << 12;
+        let data_lsb = (data[1] as u32) << 4;
+        let data_xlsb = (data[2] as u32) >> 4;
+        let pressure = data_msb | data_lsb | data_xlsb;
+
+        let data_msb = (data[3] as u32) << 12;
+        let data_lsb = (data[4] as u32) << 4;
+        let data_xlsb = (data[5] as u32) >> 4;
+        let temperature = data_msb | data_lsb | data_xlsb;
+
+        let humidity = u16::from_be_bytes([data[6], data[7]]);
+
+        Self {
+            pressure,
+            temperature,
+            humidity,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum BME280Error {
     NoData,
-    I2CError,
+    /// An I2C transaction failed, e.g. with a NACK on the address.
+    I2CError(i2c::ErrorKind),
+    /// An SPI transaction failed.
+    SpiError(spi::ErrorKind),
     InvalidChipId(u8),
     Timeout,
     NotCalibrated,
+    /// The calibration coefficients read back inconsistent or implausible.
+    InvalidCalibration,
+    /// A configuration register did not read back as written, see
+    /// [`BME280Sensor::with_write_verification`](bme280::BME280Sensor::with_write_verification).
+    ConfigurationNotAccepted,
+}
+
+impl fmt::Display for BME280Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BME280Error::NoData => f.write_str("no data"),
+            BME280Error::I2CError(kind) => write!(f, "I2C error: {kind}"),
+            BME280Error::SpiError(kind) => write!(f, "SPI error: {kind}"),
+            BME280Error::InvalidChipId(chip_id) => write!(f, "invalid chip id {chip_id:#04x}"),
+            BME280Error::Timeout => f.write_str("timeout"),
+            BME280Error::NotCalibrated => f.write_str("sensor not calibrated, call setup first"),
+            BME280Error::InvalidCalibration => f.write_str("invalid calibration coefficients"),
+            BME280Error::ConfigurationNotAccepted => {
+                f.write_str("configuration not accepted by the sensor")
+            }
+        }
+    }
+}
+
+impl core::error::Error for BME280Error {}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for BME280Error {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_display_includes_bus_error() {
+        let error = BME280Error::I2CError(i2c::ErrorKind::NoAcknowledge(
+            i2c::NoAcknowledgeSource::Address,
+        ));
+
+        assert_eq!(
+            error.to_string(),
+            "I2C error: The device did not acknowledge its address"
+        );
+        assert_eq!(
+            BME280Error::InvalidChipId(0x42).to_string(),
+            "invalid chip id 0x42"
+        );
+    }
 }
@@ -0,0 +1,207 @@
+//! Background sampling task.
+//!
+//! [`BME280Runner`] owns a sensor and its bus, samples at a fixed period and
+//! hands every reading to an `embassy_sync` [`Watch`] or [`PubSubChannel`],
+//! so several tasks can consume the same measurements:
+//!
+//! ```ignore
+//! static READINGS: Watch<CriticalSectionRawMutex, Reading, 3> = Watch::new();
+//!
+//! #[embassy_executor::task]
+//! async fn sample(mut runner: BME280Runner<RpI2c<'static, I2C0>>) -> ! {
+//!     runner.run_watch(&READINGS).await
+//! }
+//! ```
+
+use crate::bme280::BME280Sensor;
+use crate::configuration::SensorMode;
+use crate::interface::{I2cInterface, Interface};
+use crate::{BME280Error, BME280Response, ChipModel};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::pubsub::PubSubChannel;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Ticker};
+
+/// Sampling period outside normal mode unless set with
+/// [`with_period`](BME280Runner::with_period).
+const DEFAULT_FORCED_PERIOD: Duration = Duration::from_secs(1);
+
+/// Outcome of one sample, published as is so consumers see bus errors too.
+pub type Reading = Result<BME280Response, BME280Error>;
+
+/// Samples a sensor that has already been `setup`.
+///
+/// In normal mode the runner reads the data registers once per conversion
+/// period of the sensor, in sleep or forced mode it triggers a forced
+/// conversion every period.
+pub struct BME280Runner<BUS, IF = I2cInterface> {
+    sensor: BME280Sensor<IF>,
+    bus: BUS,
+    period: Option<Duration>,
+}
+
+impl<BUS, IF> BME280Runner<BUS, IF>
+where
+    IF: Interface<BUS>,
+{
+    pub fn new(sensor: BME280Sensor<IF>, bus: BUS) -> Self {
+        Self {
+            sensor,
+            bus,
+            period: None,
+        }
+    }
+
+    /// Overrides the sampling period.
+    ///
+    /// In normal mode a period shorter than
+    /// [`normal_mode_period_on`](crate::configuration::SamplingConfiguration::normal_mode_period_on)
+    /// the detected chip reads the same conversion more than once.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = Some(period);
+        self
+    }
+
+    /// Period between two samples.
+    pub fn period(&self) -> Duration {
+        let configuration = self.sensor.sampling_configuration();
+        let chip_model = self.sensor.chip_model().unwrap_or(ChipModel::BME280);
+        self.period.unwrap_or(match configuration.sensor_mode() {
+            SensorMode::Normal => configuration.normal_mode_period_on(chip_model),
+            _ => DEFAULT_FORCED_PERIOD,
+        })
+    }
+
+    /// Takes one sample.
+    pub async fn sample(&mut self) -> Reading {
+        match self.sensor.sampling_configuration().sensor_mode() {
+            SensorMode::Normal => self.sensor.read(&mut self.bus).await,
+            _ => self.sensor.measure_once(&mut self.bus).await,
+        }
+    }
+
+    /// Samples forever, passing every reading to `publish`.
+    pub async fn run(&mut self, mut publish: impl FnMut(Reading)) -> ! {
+        let mut ticker = Ticker::every(self.period());
+        loop {
+            publish(self.sample().await);
+            ticker.next().await;
+        }
+    }
+
+    /// Samples forever, sending every reading to `watch`.
+    pub async fn run_watch<M: RawMutex, const N: usize>(
+        &mut self,
+        watch: &Watch<M, Reading, N>,
+    ) -> ! {
+        let sender = watch.sender();
+        self.run(|reading| sender.send(reading)).await
+    }
+
+    /// Samples forever, publishing every reading to `channel`.
+    ///
+    /// Subscribers that fall behind lose the oldest readings.
+    pub async fn run_pubsub<M: RawMutex, const CAP: usize, const SUBS: usize, const PUBS: usize>(
+        &mut self,
+        channel: &PubSubChannel<M, Reading, CAP, SUBS, PUBS>,
+    ) -> ! {
+        let publisher = channel.immediate_publisher();
+        self.run(|reading| publisher.publish_immediate(reading))
+            .await
+    }
+
+    /// Gives back the sensor and the bus.
+    pub fn release(self) -> (BME280Sensor<IF>, BUS) {
+        (self.sensor, self.bus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::StandbyDuration;
+    use crate::simulator::{reference_configuration, Simulator, SIMULATOR_ADDRESS};
+    use crate::units::Celsius;
+    use embassy_futures::block_on;
+    use embassy_futures::select::{select, Either};
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    fn runner(sensor_mode: SensorMode) -> BME280Runner<Simulator> {
+        let mut bus = Simulator::new();
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        let configuration = reference_configuration()
+            .with_standby_duration(StandbyDuration::Millis0_5)
+            .with_sensor_mode(sensor_mode);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+        BME280Runner::new(sensor, bus)
+    }
+
+    #[test]
+    fn period_follows_normal_mode_conversions() {
+        let runner = runner(SensorMode::Normal);
+
+        assert_eq!(
+            runner.period(),
+            runner.sensor.sampling_configuration().normal_mode_period()
+        );
+        assert_eq!(
+            runner.with_period(Duration::from_secs(5)).period(),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn period_follows_bmp280_standby_duration() {
+        let mut bus = Simulator::new().with_chip_id(0x58);
+        let mut sensor = BME280Sensor::new(SIMULATOR_ADDRESS);
+        let configuration = reference_configuration()
+            .with_standby_duration(StandbyDuration::Millis10)
+            .with_sensor_mode(SensorMode::Normal);
+        block_on(sensor.setup(&mut bus, configuration)).unwrap();
+
+        let runner = BME280Runner::new(sensor, bus);
+
+        // 5.5 ms measurement without humidity plus 2000 ms standby
+        assert_eq!(runner.period(), Duration::from_micros(2_005_500));
+    }
+
+    #[test]
+    fn sample_triggers_forced_conversion() {
+        let mut runner = runner(SensorMode::Forced);
+        let conversions = runner.bus.conversions;
+
+        let response = block_on(runner.sample()).unwrap();
+
+        assert_eq!(response.temperature, Some(Celsius(25.08)));
+        assert_eq!(runner.bus.conversions, conversions + 1);
+    }
+
+    #[test]
+    fn run_watch_publishes_readings() {
+        let watch: Watch<NoopRawMutex, Reading, 1> = Watch::new();
+        let mut receiver = watch.receiver().unwrap();
+        let mut runner = runner(SensorMode::Normal);
+
+        let Either::Second(reading) =
+            block_on(select(runner.run_watch(&watch), receiver.changed()));
+
+        assert_eq!(reading.unwrap().temperature, Some(Celsius(25.08)));
+    }
+
+    #[test]
+    fn run_pubsub_publishes_every_sample() {
+        let channel: PubSubChannel<NoopRawMutex, Reading, 4, 1, 0> = PubSubChannel::new();
+        let mut subscriber = channel.subscriber().unwrap();
+        let mut runner = runner(SensorMode::Forced).with_period(Duration::from_millis(10));
+
+        let received = async {
+            subscriber.next_message_pure().await.unwrap();
+            subscriber.next_message_pure().await
+        };
+        let Either::Second(reading) = block_on(select(runner.run_pubsub(&channel), received));
+
+        assert!(reading.is_ok());
+        let (_, bus) = runner.release();
+        assert!(bus.conversions >= 2);
+    }
+}
@@ -0,0 +1,117 @@
+//! Sharing one I2C peripheral between several devices and tasks.
+//!
+//! The sensor only needs an `embedded_hal_async::i2c::I2c`, so an
+//! [`I2cDevice`] handed out from a mutex-protected bus works like the
+//! peripheral itself. Each task gets its own device; the mutex serializes
+//! the transactions, and a `BME280Sensor` never holds the bus between calls.
+//!
+//! ```ignore
+//! static I2C_BUS: OnceLock<SharedI2cBus<CriticalSectionRawMutex, RpI2c<'static, I2C0>>> =
+//!     OnceLock::new();
+//!
+//! let bus = I2C_BUS.get_or_init(|| SharedI2cBus::new(i2c));
+//! let mut indoor = I2cDevice::new(bus);
+//! let mut outdoor = I2cDevice::new(bus);
+//! ```
+
+pub use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
+
+/// Bus shared by the [`I2cDevice`]s of all devices on one peripheral.
+pub type SharedI2cBus<M, BUS> = embassy_sync::mutex::Mutex<M, BUS>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bme280::BME280Sensor;
+    use crate::configuration::SensorMode;
+    use crate::runner::{BME280Runner, Reading};
+    use crate::simulator::{
+        reference_configuration, SimulatedBoard, Simulator, REFERENCE_ADC_H, REFERENCE_ADC_P,
+        REFERENCE_ADC_T,
+    };
+    use crate::units::Celsius;
+    use crate::BME280Response;
+    use embassy_futures::block_on;
+    use embassy_futures::join::join3;
+    use embassy_futures::select::{select, Either};
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embassy_sync::pubsub::PubSubChannel;
+    use embassy_time::Duration;
+    use embedded_hal_async::i2c::I2c;
+
+    const PRIMARY: u8 = 0x76;
+    const SECONDARY: u8 = 0x77;
+    const DISPLAY: u8 = 0x3C;
+
+    fn board() -> SimulatedBoard {
+        let primary = Simulator::new();
+        let mut secondary = Simulator::new();
+        secondary.address = SECONDARY;
+        secondary.set_adc(REFERENCE_ADC_P, REFERENCE_ADC_T + 10_000, REFERENCE_ADC_H);
+        SimulatedBoard {
+            sensors: vec![primary, secondary],
+            other_address: DISPLAY,
+            other_writes: Vec::new(),
+        }
+    }
+
+    async fn measure<I: I2c>(address: u8, mut bus: I) -> BME280Response {
+        let mut sensor = BME280Sensor::new(address);
+        sensor
+            .setup(
+                &mut bus,
+                reference_configuration().with_sensor_mode(SensorMode::Forced),
+            )
+            .await
+            .unwrap();
+        sensor.measure_once(&mut bus).await.unwrap()
+    }
+
+    #[test]
+    fn sensors_and_other_device_share_one_bus() {
+        let bus: SharedI2cBus<NoopRawMutex, _> = SharedI2cBus::new(board());
+
+        let (primary, secondary, display) = block_on(join3(
+            measure(PRIMARY, I2cDevice::new(&bus)),
+            measure(SECONDARY, I2cDevice::new(&bus)),
+            async {
+                let mut display = I2cDevice::new(&bus);
+                display.write(DISPLAY, &[0x00, 0xAF]).await
+            },
+        ));
+
+        assert_eq!(primary.temperature, Some(Celsius(25.08)));
+        assert!(secondary.temperature > primary.temperature);
+        assert!(display.is_ok());
+        assert_eq!(bus.into_inner().other_writes, [0x00, 0xAF]);
+    }
+
+    #[test]
+    fn runners_share_one_bus() {
+        let bus: SharedI2cBus<NoopRawMutex, _> = SharedI2cBus::new(board());
+        let channel: PubSubChannel<NoopRawMutex, Reading, 4, 1, 0> = PubSubChannel::new();
+        let mut subscriber = channel.subscriber().unwrap();
+        let runner = |address: u8| {
+            let mut device = I2cDevice::new(&bus);
+            let mut sensor = BME280Sensor::new(address);
+            block_on(sensor.setup(
+                &mut device,
+                reference_configuration().with_sensor_mode(SensorMode::Forced),
+            ))
+            .unwrap();
+            BME280Runner::new(sensor, device).with_period(Duration::from_millis(10))
+        };
+        let mut primary = runner(PRIMARY);
+        let mut secondary = runner(SECONDARY);
+
+        let received = async {
+            let first = subscriber.next_message_pure().await.unwrap();
+            let second = subscriber.next_message_pure().await.unwrap();
+            (first, second)
+        };
+        let runners = select(primary.run_pubsub(&channel), secondary.run_pubsub(&channel));
+        let Either::Second((first, second)) = block_on(select(runners, received));
+
+        assert_ne!(first.temperature, second.temperature);
+    }
+}
@@ -0,0 +1,360 @@
+//! Register level BME280 simulator used by the host-side tests.
+//!
+//! Implements the async I2C and SPI traits on top of a 256 byte register map,
+//! so the driver can be exercised with `cargo test` without hardware.
+
+use crate::configuration::{Oversampling, SamplingConfiguration};
+use embedded_hal_async::i2c::{
+    ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress,
+};
+use embedded_hal_async::spi::{self, SpiDevice};
+
+pub(crate) const SIMULATOR_ADDRESS: u8 = 0x76;
+
+/// Calibration coefficients from the Bosch datasheet example
+/// (dig_T1..dig_P9) completed with typical humidity coefficients.
+pub(crate) const REFERENCE_CALIBRATION: [u8; 33] = [
+    0x70, 0x6B, // dig_T1 = 27504
+    0x43, 0x67, // dig_T2 = 26435
+    0x18, 0xFC, // dig_T3 = -1000
+    0x7D, 0x8E, // dig_P1 = 36477
+    0x43, 0xD6, // dig_P2 = -10685
+    0xD0, 0x0B, // dig_P3 = 3024
+    0x27, 0x0B, // dig_P4 = 2855
+    0x8C, 0x00, // dig_P5 = 140
+    0xF9, 0xFF, // dig_P6 = -7
+    0x8C, 0x3C, // dig_P7 = 15500
+    0xF8, 0xC6, // dig_P8 = -14600
+    0x70, 0x17, // dig_P9 = 6000
+    0x00, // 0xA0, reserved
+    0x4B, // dig_H1 = 75
+    0x6A, 0x01, // dig_H2 = 362
+    0x00, // dig_H3 = 0
+    0x13, 0x09, 0x00, // dig_H4 = 313, dig_H5 = 0
+    0x1E, // dig_H6 = 30
+];
+
+/// Raw ADC values matching the datasheet example: 25.08 °C, 100653 Pa.
+pub(crate) const REFERENCE_ADC_T: u32 = 519_888;
+pub(crate) const REFERENCE_ADC_P: u32 = 415_148;
+pub(crate) const REFERENCE_ADC_H: u16 = 30_000;
+
+/// x1 oversampling on every channel in sleep mode, the quickest conversion
+/// that still measures all three channels.
+pub(crate) fn reference_configuration() -> SamplingConfiguration {
+    SamplingConfiguration::default()
+        .with_temperature_oversampling(Oversampling::X1)
+        .with_pressure_oversampling(Oversampling::X1)
+        .with_humidity_oversampling(Oversampling::X1)
+}
+
+const REGISTER_CALIBRATION: usize = 0x88;
+const REGISTER_CHIPID: usize = 0xD0;
+const REGISTER_SOFTRESET: usize = 0xE0;
+const REGISTER_STATUS: usize = 0xF3;
+const REGISTER_CONTROL: usize = 0xF4;
+const REGISTER_CONFIG: usize = 0xF5;
+const REGISTER_DATA: usize = 0xF7;
+const SOFTRESET_COMMAND: u8 = 0xB6;
+const STATUS_IM_UPDATE: u8 = 0b0000_0001;
+const STATUS_MEASURING: u8 = 0b0000_1000;
+const MODE_MASK: u8 = 0b11;
+const MODE_NORMAL: u8 = 0b11;
+const CONFIG_SPI3W_EN: u8 = 0b1;
+const SPI_READ: u8 = 0x80;
+
+pub(crate) struct Simulator {
+    pub(crate) address: u8,
+    pub(crate) registers: [u8; 256],
+    /// Every `(register, value)` pair written by the driver, in order.
+    pub(crate) writes: Vec<(u8, u8)>,
+    /// Number of status reads that report an NVM copy after a soft reset.
+    pub(crate) nvm_copy_reads: usize,
+    /// Number of status reads that report `measuring` after a forced
+    /// conversion is triggered.
+    pub(crate) conversion_reads: usize,
+    /// Number of calibration block reads (starting at 0x88) returning 0xFF.
+    pub(crate) calibration_faults: usize,
+    /// Number of configuration register writes (0xF2, 0xF4, 0xF5) the chip
+    /// ignores, as if corrupted on the wire.
+    pub(crate) dropped_writes: usize,
+    /// When set, every transaction fails with this error.
+    pub(crate) fault: Option<ErrorKind>,
+    pub(crate) resets: usize,
+    pub(crate) conversions: usize,
+    /// Wired for 3-wire SPI: the chip only answers once spi3w_en is set,
+    /// until then reads see the idle line (0xFF).
+    pub(crate) three_wire: bool,
+    /// First byte of every SPI transaction, the register address with bit 7
+    /// set for reads and cleared for writes.
+    pub(crate) spi_control_bytes: Vec<u8>,
+    /// Data registers the next conversion will produce.
+    measurement: [u8; 8],
+    pointer: usize,
+    nvm_copy_remaining: usize,
+    conversion_remaining: usize,
+}
+
+impl Simulator {
+    pub(crate) fn new() -> Self {
+        let mut simulator = Self {
+            address: SIMULATOR_ADDRESS,
+            registers: [0; 256],
+            writes: Vec::new(),
+            nvm_copy_reads: 0,
+            conversion_reads: 2,
+            calibration_faults: 0,
+            dropped_writes: 0,
+            fault: None,
+            resets: 0,
+            conversions: 0,
+            three_wire: false,
+            spi_control_bytes: Vec::new(),
+            measurement: [0; 8],
+            pointer: 0,
+            nvm_copy_remaining: 0,
+            conversion_remaining: 0,
+        };
+        simulator.registers[REGISTER_CHIPID] = 0x60;
+        simulator.set_calibration(REFERENCE_CALIBRATION);
+        simulator.set_adc(REFERENCE_ADC_P, REFERENCE_ADC_T, REFERENCE_ADC_H);
+        simulator.complete_conversion();
+        simulator
+    }
+
+    pub(crate) fn with_chip_id(mut self, chip_id: u8) -> Self {
+        self.registers[REGISTER_CHIPID] = chip_id;
+        self
+    }
+
+    /// Lays out the 33 calibration bytes at 0x88..=0xA1 and 0xE1..=0xE7.
+    pub(crate) fn set_calibration(&mut self, calibration: [u8; 33]) {
+        self.registers[0x88..=0xA1].copy_from_slice(&calibration[..26]);
+        self.registers[0xE1..=0xE7].copy_from_slice(&calibration[26..]);
+    }
+
+    /// Sets the raw 20-bit pressure/temperature and 16-bit humidity readings
+    /// the next conversion stores in the data registers 0xF7..=0xFE.
+    /// In normal mode they show up on the next data read.
+    pub(crate) fn set_adc(&mut self, adc_p: u32, adc_t: u32, adc_h: u16) {
+        let data = &mut self.measurement;
+        data[0] = (adc_p >> 12) as u8;
+        data[1] = (adc_p >> 4) as u8;
+        data[2] = ((adc_p & 0xF) << 4) as u8;
+        data[3] = (adc_t >> 12) as u8;
+        data[4] = (adc_t >> 4) as u8;
+        data[5] = ((adc_t & 0xF) << 4) as u8;
+        data[6..8].copy_from_slice(&adc_h.to_be_bytes());
+    }
+
+    pub(crate) fn register(&self, register: u8) -> u8 {
+        self.registers[register as usize]
+    }
+
+    fn mode(&self) -> u8 {
+        self.registers[REGISTER_CONTROL] & MODE_MASK
+    }
+
+    fn start_conversion(&mut self) {
+        self.conversion_remaining = self.conversion_reads;
+        if self.conversion_remaining == 0 {
+            self.complete_conversion();
+        }
+    }
+
+    fn complete_conversion(&mut self) {
+        self.conversions += 1;
+        self.registers[REGISTER_DATA..REGISTER_DATA + 8].copy_from_slice(&self.measurement);
+        if self.mode() != MODE_NORMAL {
+            self.registers[REGISTER_CONTROL] &= !MODE_MASK;
+        }
+    }
+
+    fn soft_reset(&mut self) {
+        self.resets += 1;
+        for register in 0xF2..=0xF5 {
+            self.registers[register] = 0;
+        }
+        self.nvm_copy_remaining = self.nvm_copy_reads;
+    }
+
+    fn read(&mut self) -> u8 {
+        if self.pointer == REGISTER_DATA && self.mode() == MODE_NORMAL {
+            self.complete_conversion();
+        }
+        let value = if self.pointer == REGISTER_STATUS && self.nvm_copy_remaining > 0 {
+            self.nvm_copy_remaining -= 1;
+            self.registers[REGISTER_STATUS] | STATUS_IM_UPDATE
+        } else if self.pointer == REGISTER_STATUS && self.conversion_remaining > 0 {
+            self.conversion_remaining -= 1;
+            if self.conversion_remaining == 0 {
+                self.complete_conversion();
+            }
+            self.registers[REGISTER_STATUS] | STATUS_MEASURING
+        } else {
+            self.registers[self.pointer]
+        };
+        self.pointer = (self.pointer + 1) & 0xFF;
+        value
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) {
+        if self.pointer == REGISTER_CALIBRATION && self.calibration_faults > 0 {
+            self.calibration_faults -= 1;
+            buffer.fill(0xFF);
+        } else {
+            buffer.iter_mut().for_each(|b| *b = self.read());
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if let [register] = bytes {
+            self.pointer = *register as usize;
+            return;
+        }
+        for pair in bytes.chunks(2) {
+            if let [register, value] = *pair {
+                self.writes.push((register, value));
+                if matches!(register, 0xF2 | 0xF4 | 0xF5) && self.dropped_writes > 0 {
+                    self.dropped_writes -= 1;
+                    continue;
+                }
+                match register as usize {
+                    REGISTER_SOFTRESET => {
+                        if value == SOFTRESET_COMMAND {
+                            self.soft_reset();
+                        }
+                    }
+                    REGISTER_CONTROL => {
+                        self.registers[REGISTER_CONTROL] = value;
+                        if matches!(value & MODE_MASK, 0b01 | 0b10) {
+                            self.start_conversion();
+                        }
+                    }
+                    0xF2 | 0xF5 => self.registers[register as usize] = value,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl ErrorType for Simulator {
+    type Error = ErrorKind;
+}
+
+impl I2c<SevenBitAddress> for Simulator {
+    async fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if let Some(fault) = self.fault {
+            return Err(fault);
+        }
+        if address != self.address {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => self.write(bytes),
+                Operation::Read(buffer) => self.read_into(buffer),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl spi::ErrorType for Simulator {
+    type Error = spi::ErrorKind;
+}
+
+/// Every register address has bit 7 set, so on SPI bit 7 of the control byte
+/// selects a burst read and the chip restores it for writes. A write
+/// transaction may chain several control/data pairs.
+impl SpiDevice for Simulator {
+    async fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), spi::ErrorKind> {
+        if self.fault.is_some() {
+            return Err(spi::ErrorKind::Other);
+        }
+        let mut command = Vec::new();
+        for operation in operations {
+            match operation {
+                spi::Operation::Write(bytes) => command.extend_from_slice(bytes),
+                spi::Operation::Read(buffer) => match command.first() {
+                    Some(&control) if control & SPI_READ != 0 => {
+                        self.pointer = control as usize;
+                        self.read_into(buffer);
+                        if self.three_wire && self.registers[REGISTER_CONFIG] & CONFIG_SPI3W_EN == 0
+                        {
+                            buffer.fill(0xFF);
+                        }
+                    }
+                    _ => buffer.fill(0xFF),
+                },
+                spi::Operation::DelayNs(_) => {}
+                // The driver never transfers in full duplex.
+                spi::Operation::Transfer(..) | spi::Operation::TransferInPlace(_) => {
+                    return Err(spi::ErrorKind::Other);
+                }
+            }
+        }
+        let Some(&control) = command.first() else {
+            return Ok(());
+        };
+        self.spi_control_bytes.push(control);
+        // Data sent after a read command is ignored.
+        if control & SPI_READ == 0 {
+            let pairs: Vec<u8> = command
+                .chunks(2)
+                .flat_map(|pair| match *pair {
+                    [control, value] => vec![control | SPI_READ, value],
+                    _ => Vec::new(),
+                })
+                .collect();
+            self.write(&pairs);
+        }
+        Ok(())
+    }
+}
+
+/// Several devices on one I2C bus: simulated sensors plus a write-only
+/// device standing in for e.g. a display.
+pub(crate) struct SimulatedBoard {
+    pub(crate) sensors: Vec<Simulator>,
+    pub(crate) other_address: u8,
+    /// Bytes written to the device at `other_address`.
+    pub(crate) other_writes: Vec<u8>,
+}
+
+impl ErrorType for SimulatedBoard {
+    type Error = ErrorKind;
+}
+
+impl I2c<SevenBitAddress> for SimulatedBoard {
+    async fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address == self.other_address {
+            for operation in operations {
+                if let Operation::Write(bytes) = operation {
+                    self.other_writes.extend_from_slice(bytes);
+                }
+            }
+            return Ok(());
+        }
+        match self
+            .sensors
+            .iter_mut()
+            .find(|sensor| sensor.address == address)
+        {
+            Some(sensor) => I2c::transaction(sensor, address, operations).await,
+            None => Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Unit wrappers for measurements.
+//!
+//! [`BME280Response`](crate::BME280Response) reports [`Celsius`],
+//! [`RelativeHumidity`], [`Pascal`] and [`Meters`]; the other units convert
+//! from and to those with `From`/`Into`. [`AbsoluteHumidity`] and
+//! [`MixingRatio`] are results of the [`derived`](crate::derived) module:
+//!
+//! ```
+//! use embassy_bme280_sensor::units::{Celsius, Fahrenheit, Hectopascal, Pascal};
+//!
+//! let temperature: Fahrenheit = Celsius(25.0).into();
+//! let pressure = Hectopascal::from(Pascal(101_325.0));
+//! assert_eq!(temperature, Fahrenheit(77.0));
+//! assert_eq!(pressure, Hectopascal(1013.25));
+//! ```
+
+pub(crate) const ZERO_CELSIUS_IN_KELVIN: f32 = 273.15;
+const PASCAL_PER_HECTOPASCAL: f32 = 100.0;
+const PASCAL_PER_INCH_OF_MERCURY: f32 = 3386.389;
+const PASCAL_PER_MILLIMETER_OF_MERCURY: f32 = 133.322_39;
+const METERS_PER_FOOT: f32 = 0.3048;
+
+/// Temperature in °C.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Celsius(pub f32);
+
+/// Temperature in °F.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Fahrenheit(pub f32);
+
+/// Temperature in K.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Kelvin(pub f32);
+
+/// Pressure in Pa.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Pascal(pub f32);
+
+/// Pressure in hPa, which is the same as mbar.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Hectopascal(pub f32);
+
+/// Pressure in inches of mercury (inHg).
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct InchesOfMercury(pub f32);
+
+/// Pressure in millimeters of mercury (mmHg).
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct MillimetersOfMercury(pub f32);
+
+/// Relative humidity in %.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct RelativeHumidity(pub f32);
+
+/// Absolute humidity in g/m³.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct AbsoluteHumidity(pub f32);
+
+/// Mixing ratio in grams of water vapour per kilogram of dry air.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct MixingRatio(pub f32);
+
+/// Altitude in m.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Meters(pub f32);
+
+/// Altitude in ft.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Feet(pub f32);
+
+/// Implements `From` in both directions between a unit and the unit the
+/// driver reports.
+macro_rules! conversion {
+    ($base:ident, $unit:ident, |$to:ident| $to_unit:expr, |$from:ident| $from_unit:expr) => {
+        impl From<$base> for $unit {
+            fn from($base($to): $base) -> Self {
+                $unit($to_unit)
+            }
+        }
+
+        impl From<$unit> for $base {
+            fn from($unit($from): $unit) -> Self {
+                $base($from_unit)
+            }
+        }
+    };
+}
+
+conversion!(
+    Celsius,
+    Fahrenheit,
+    |celsius| celsius * 9.0 / 5.0 + 32.0,
+    |fahrenheit| (fahrenheit - 32.0) * 5.0 / 9.0
+);
+conversion!(
+    Celsius,
+    Kelvin,
+    |celsius| celsius + ZERO_CELSIUS_IN_KELVIN,
+    |kelvin| kelvin - ZERO_CELSIUS_IN_KELVIN
+);
+conversion!(
+    Pascal,
+    Hectopascal,
+    |pascal| pascal / PASCAL_PER_HECTOPASCAL,
+    |hectopascal| hectopascal * PASCAL_PER_HECTOPASCAL
+);
+conversion!(
+    Pascal,
+    InchesOfMercury,
+    |pascal| pascal / PASCAL_PER_INCH_OF_MERCURY,
+    |inches| inches * PASCAL_PER_INCH_OF_MERCURY
+);
+conversion!(
+    Pascal,
+    MillimetersOfMercury,
+    |pascal| pascal / PASCAL_PER_MILLIMETER_OF_MERCURY,
+    |millimeters| millimeters * PASCAL_PER_MILLIMETER_OF_MERCURY
+);
+conversion!(Meters, Feet, |meters| meters / METERS_PER_FOOT, |feet| feet
+    * METERS_PER_FOOT);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_conversions() {
+        assert_eq!(Fahrenheit::from(Celsius(100.0)), Fahrenheit(212.0));
+        assert_eq!(Celsius::from(Fahrenheit(-40.0)), Celsius(-40.0));
+        assert_eq!(Kelvin::from(Celsius(0.0)), Kelvin(273.15));
+        assert_eq!(Celsius::from(Kelvin(273.15)), Celsius(0.0));
+    }
+
+    #[test]
+    fn pressure_conversions() {
+        assert_eq!(Hectopascal::from(Pascal(101_325.0)), Hectopascal(1013.25));
+        assert!((InchesOfMercury::from(Pascal(101_325.0)).0 - 29.921).abs() < 0.001);
+        assert!((MillimetersOfMercury::from(Pascal(101_325.0)).0 - 760.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn altitude_conversions() {
+        assert!((Feet::from(Meters(1000.0)).0 - 3280.84).abs() < 0.01);
+        assert!((Meters::from(Feet(1000.0)).0 - 304.8).abs() < 0.001);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for value in [-40.0, 0.0, 25.08, 85.0] {
+            let celsius = Celsius(value);
+            let fahrenheit: Fahrenheit = celsius.into();
+            let kelvin: Kelvin = celsius.into();
+
+            assert!((Celsius::from(fahrenheit).0 - value).abs() < 1e-5);
+            assert!((Celsius::from(kelvin).0 - value).abs() < 1e-4);
+        }
+        for value in [30_000.0, 100_653.27, 110_000.0] {
+            let pascal = Pascal(value);
+            let inches: InchesOfMercury = pascal.into();
+            let millimeters: MillimetersOfMercury = pascal.into();
+
+            assert!((Pascal::from(inches).0 - value).abs() < 0.01);
+            assert!((Pascal::from(millimeters).0 - value).abs() < 0.01);
+        }
+    }
+}